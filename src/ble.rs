@@ -20,8 +20,8 @@
 //! ble.bt_le_adv_start(&param, &ad, &sd).await?;
 //! ```
 
-use crate::packet::{CborError, PacketBuilder};
-use crate::{AsyncTransport, RpcClient, RpcError};
+use crate::packet::{CborError, HEADER_LEN, PacketBuilder, PacketDecoder};
+use crate::{AsyncTransport, InitInfo, PacketObserver, RpcClient, RpcError};
 
 // ============================================================================
 // Ble Struct
@@ -30,8 +30,21 @@ use crate::{AsyncTransport, RpcClient, RpcError};
 /// BLE RPC client
 ///
 /// Encapsulates an RPC client for Bluetooth Low Energy operations.
-pub struct Ble<T: AsyncTransport> {
-    client: RpcClient<T>,
+///
+/// `RX` is the size of the buffer responses are received into, allocated on
+/// the stack for each command. The default of 256 bytes fits every response
+/// the current commands produce; see [`Ble::with_buffer_size`] to change it.
+///
+/// # Send and Sync
+///
+/// `Ble<T>` is `Send` when `T` and `T::Error` are, so it can be moved into
+/// another task. It is `Sync` under the same conditions plus `T: Sync`, but
+/// every command takes `&mut self`, so sharing it needs a lock; with the
+/// `embassy-sync` feature, `SharedBle` provides one.
+pub struct Ble<T: AsyncTransport, const RX: usize = 256> {
+    client: RpcClient<T, RX>,
+    /// Parameters used by `bt_le_adv_start_default`
+    default_adv_param: BtLeAdvParam,
 }
 
 impl<T: AsyncTransport> Ble<T> {
@@ -44,31 +57,312 @@ impl<T: AsyncTransport> Ble<T> {
     /// let mut ble = Ble::new(transport).await?;
     /// ```
     pub async fn new(transport: T) -> Result<Self, RpcError> {
+        Self::with_buffer_size(transport).await
+    }
+
+    /// Create a new BLE client, resending the init packets until the remote
+    /// answers
+    ///
+    /// Useful when both processors start asynchronously and the remote may
+    /// miss the first init packets. `delay` is called once per attempt and
+    /// must return a fresh future that completes when the attempt should be
+    /// abandoned. Fails with `RpcError::Timeout` once the retries run out.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut ble = Ble::new_with_retry(transport, || Timer::after_millis(100)).await?;
+    /// ```
+    pub async fn new_with_retry<D: Future>(
+        transport: T,
+        delay: impl FnMut() -> D,
+    ) -> Result<Self, RpcError> {
+        let mut client = RpcClient::new(transport);
+        client.init_with_retry(delay).await?;
+        Ok(Self::from_client(client))
+    }
+
+    /// Create a new BLE client, first discarding stale receive data
+    ///
+    /// Use after a reset of this side, when bytes from before the reset would
+    /// otherwise corrupt the init exchange. `scratch` is only used while
+    /// draining; see `drain_rx`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut scratch = [0u8; 64];
+    /// let mut ble = Ble::new_drained(transport, &mut scratch).await?;
+    /// ```
+    pub async fn new_drained(transport: T, scratch: &mut [u8]) -> Result<Self, RpcError> {
+        let mut client = RpcClient::new(transport);
+        client.drain_rx(scratch).await?;
+        client.init().await?;
+        Ok(Self::from_client(client))
+    }
+}
+
+impl<T: AsyncTransport, const RX: usize> Ble<T, RX> {
+    /// Create a new BLE client with an `RX`-byte response buffer
+    ///
+    /// Use a smaller buffer on RAM-constrained parts, or a larger one when
+    /// responses may exceed 256 bytes.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut ble = Ble::<_, 128>::with_buffer_size(transport).await?;
+    /// ```
+    pub async fn with_buffer_size(transport: T) -> Result<Self, RpcError> {
         let mut client = RpcClient::new(transport);
         client.init().await?;
-        Ok(Self { client })
+        Ok(Self::from_client(client))
+    }
+
+    /// Create a BLE client without performing the RPC handshake
+    ///
+    /// For remotes that are already initialized, e.g. after a warm reset of
+    /// this side only, where repeating the handshake would confuse the remote.
+    /// The group IDs must be the ones the remote assigned during the original
+    /// handshake.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let info = ble.init_info();
+    /// // ... warm reset ...
+    /// let mut ble: Ble<_> =
+    ///     Ble::from_parts(transport, info.bt_rpc_group_id, info.rpc_utils_group_id);
+    /// ```
+    pub fn from_parts(transport: T, bt_rpc_group_id: u8, rpc_utils_group_id: u8) -> Self {
+        Self::from_client(RpcClient::from_parts(
+            transport,
+            bt_rpc_group_id,
+            rpc_utils_group_id,
+        ))
+    }
+
+    fn from_client(client: RpcClient<T, RX>) -> Self {
+        Self {
+            client,
+            default_adv_param: BtLeAdvParam::connectable(),
+        }
+    }
+
+    /// Group IDs and protocol version negotiated during the RPC handshake
+    ///
+    /// Useful for logging what the remote registered.
+    pub fn init_info(&self) -> InitInfo {
+        self.client.init_info()
+    }
+
+    /// Recover the transport, e.g. to reuse the UART for something else
+    ///
+    /// The remote is left as it is; nothing is sent.
+    pub fn into_inner(self) -> T {
+        self.client.into_inner()
+    }
+
+    /// Read and discard everything the transport has buffered
+    ///
+    /// Returns the number of bytes discarded. Reads until the transport
+    /// reports no data, so wrap it in `with_timeout` if `read` waits instead.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut scratch = [0u8; 64];
+    /// let flushed = ble.drain_rx(&mut scratch).await?;
+    /// ```
+    pub async fn drain_rx(&mut self, scratch: &mut [u8]) -> Result<usize, RpcError> {
+        self.client.drain_rx(scratch).await
+    }
+
+    /// Set the parameters `bt_le_adv_start_default` advertises with
+    ///
+    /// Defaults to `BtLeAdvParam::connectable()`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let param = BtLeAdvParam::builder().interval_min(0x0640).interval_max(0x0780).build();
+    /// let mut ble = Ble::new(transport).await?.with_adv_param(param);
+    /// ```
+    pub fn with_adv_param(mut self, param: BtLeAdvParam) -> Self {
+        self.default_adv_param = param;
+        self
+    }
+
+    /// Take the transport error behind the last `RpcError::Transport`
+    ///
+    /// # Example
+    /// ```ignore
+    /// if let Err(RpcError::Transport) = ble.bt_enable().await {
+    ///     let cause = ble.take_transport_error();
+    ///     defmt::error!("UART failed: {}", cause);
+    /// }
+    /// ```
+    pub fn take_transport_error(&mut self) -> Option<T::Error> {
+        self.client.take_transport_error()
+    }
+
+    /// Report every packet sent or received to `observer`
+    ///
+    /// # Example
+    /// ```ignore
+    /// static OBSERVER: HexDump = HexDump;
+    /// let mut ble = Ble::new(transport).await?.with_observer(&OBSERVER);
+    /// ```
+    pub fn with_observer(self, observer: &'static dyn PacketObserver) -> Self {
+        Self {
+            client: self.client.with_observer(observer),
+            ..self
+        }
+    }
+
+    /// Reject connection handles the remote has not reported as connected
+    ///
+    /// Connections are tracked from the `Connected` and `Disconnected` events
+    /// the client processes, so commands that take a `ConnHandle` fail with
+    /// `RpcError::InvalidHandle` instead of a remote error when given a stale
+    /// handle. `bt_conn_unref` is not checked, since it is called after the
+    /// connection is gone. Leave this off when handles are managed elsewhere.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut ble = Ble::new(transport).await?.with_conn_validation();
+    /// ```
+    pub fn with_conn_validation(self) -> Self {
+        Self {
+            client: self.client.with_conn_validation(),
+            ..self
+        }
+    }
+
+    /// Pass every event received from now on to `handler`
+    ///
+    /// Events that arrive while a command is waiting for its response are
+    /// dispatched too, so none are lost between calls to `poll_events`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.set_event_handler(|event| {
+    ///     if let Event::Disconnected { conn, reason } = event {
+    ///         defmt::info!("conn {} lost: {}", conn, reason);
+    ///     }
+    /// });
+    /// ```
+    pub fn set_event_handler(&mut self, handler: fn(Event<'_>)) {
+        self.client.set_event_handler(handler);
+    }
+
+    /// Acknowledge and dispatch every event the remote has already queued
+    ///
+    /// Returns once the transport has no more data, so it can be called from
+    /// the main loop between commands. Init packets the remote sends, e.g.
+    /// after it resets, are answered as well.
+    ///
+    /// # Example
+    /// ```ignore
+    /// loop {
+    ///     ble.poll_events().await?;
+    ///     Timer::after_millis(10).await;
+    /// }
+    /// ```
+    pub async fn poll_events(&mut self) -> Result<(), RpcError> {
+        self.client.poll_events().await
     }
 
-    /// Enable Bluetooth (TODO) add zephyr doc comments HERE
+    /// Enable Bluetooth
+    ///
+    /// Returns the Zephyr error code reported by the remote. It only reports
+    /// whether enabling started; the stack is ready once the remote reports
+    /// it, so call `bt_ready` before advertising or scanning.
     ///
     /// # Example
     /// ```ignore
     /// ble.bt_enable().await?;
+    /// ble.bt_ready().await?;
     /// ```
     pub async fn bt_enable(&mut self) -> Result<i32, RpcError> {
-        let packet = PacketBuilder::<64>::new()
-            .command(
-                self.client.context_id(),
-                BT_ENABLE_RPC_CMD,
-                0xFF,
-                self.client.bt_rpc_group_id(),
-                self.client.bt_rpc_group_id(),
-            )
+        let mut packet = self
+            .command::<64>(BT_ENABLE_RPC_CMD)
             .cbor_uint(28)?
             .cbor_uint(28)?
             .cbor_null()?;
 
-        self.client.send_command(packet.as_slice()).await
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Wait until the remote reports that Bluetooth is ready
+    ///
+    /// Completes on the `Event::Ready` that follows `bt_enable`, failing with
+    /// `RpcError::Remote` if initialization failed. Other events received while
+    /// waiting are acknowledged and passed to the event handler.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_enable().await?;
+    /// ble.bt_ready().await?;
+    /// ble.bt_le_adv_start(&param, &ad, &[]).await?;
+    /// ```
+    pub async fn bt_ready(&mut self) -> Result<(), RpcError> {
+        let mut buf = [0u8; RX];
+        let len = self
+            .client
+            .wait_event(BT_READY_CB_RPC_EVT, &mut buf)
+            .await?;
+
+        match decode_event(BT_READY_CB_RPC_EVT, &buf[HEADER_LEN..len])? {
+            Event::Ready { err: 0 } => Ok(()),
+            Event::Ready { err } => Err(RpcError::Remote(err)),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Disable Bluetooth
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_disable().await?;
+    /// ```
+    pub async fn bt_disable(&mut self) -> Result<i32, RpcError> {
+        let mut packet = self.command::<64>(BT_DISABLE_RPC_CMD).cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Set the Bluetooth device name
+    ///
+    /// Names that do not fit in the command packet are rejected with
+    /// `RpcError::Cbor(CborError::BufferTooSmall)`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_set_name("MyDevice").await?;
+    /// ```
+    pub async fn bt_set_name(&mut self, name: &str) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_SET_NAME_RPC_CMD)
+            .cbor_str(name)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Get the Bluetooth device name
+    ///
+    /// The name is copied into `out`. Returns `RpcError::InvalidResponse` if
+    /// the remote name does not fit.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut buf = [0u8; 32];
+    /// let name = ble.bt_get_name(&mut buf).await?;
+    /// ```
+    pub async fn bt_get_name<'b>(&mut self, out: &'b mut [u8]) -> Result<&'b str, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_GET_NAME_OUT_RPC_CMD)
+            .cbor_uint(out.len() as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_str(packet.as_mut_slice(), out)
+            .await
     }
 
     /// Start BLE advertising
@@ -86,7 +380,7 @@ impl<T: AsyncTransport> Ble<T> {
         ad: &[BtData<'a>],
         sd: &[BtData<'a>],
     ) -> Result<i32, RpcError> {
-        let packet = encode_bt_le_adv_start::<256>(
+        let mut packet = encode_bt_le_adv_start::<256>(
             self.client.context_id(),
             self.client.bt_rpc_group_id(),
             self.client.bt_rpc_group_id(),
@@ -95,91 +389,1623 @@ impl<T: AsyncTransport> Ble<T> {
             sd,
         )?;
 
-        self.client.send_command(packet.as_slice()).await
+        self.client.send_command(packet.as_mut_slice()).await
     }
-}
 
-// ============================================================================
-// Constants
-// ============================================================================
+    /// Start BLE advertising with the parameters set by `with_adv_param`
+    ///
+    /// # Example
+    /// ```ignore
+    /// let ad = [BtData::flags(&[BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR])];
+    /// ble.bt_le_adv_start_default(&ad, &[]).await?;
+    /// ```
+    pub async fn bt_le_adv_start_default<'a>(
+        &mut self,
+        ad: &[BtData<'a>],
+        sd: &[BtData<'a>],
+    ) -> Result<i32, RpcError> {
+        let param = self.default_adv_param;
+        self.bt_le_adv_start(&param, ad, sd).await
+    }
 
-/// BLE advertising options
-pub const BT_LE_ADV_OPT_CONNECTABLE: u32 = 0x00000001;
-pub const BT_LE_ADV_OPT_ONE_TIME: u32 = 0x00000002;
+    /// Start BLE advertising with pre-formatted advertising data
+    ///
+    /// `ad` and `sd` are complete AD structures (length, type, data) as they
+    /// appear on air, e.g. blobs carried over from existing advertising code.
+    /// They are sent as-is rather than split into `BtData` elements.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let ad = [0x02, BT_DATA_FLAGS, BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR];
+    /// ble.bt_le_adv_start_raw(&BtLeAdvParam::connectable(), &ad, &[]).await?;
+    /// ```
+    pub async fn bt_le_adv_start_raw(
+        &mut self,
+        param: &BtLeAdvParam,
+        ad: &[u8],
+        sd: &[u8],
+    ) -> Result<i32, RpcError> {
+        let mut packet = encode_bt_le_adv_start_raw::<256>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            param,
+            ad,
+            sd,
+        )?;
 
-/// BLE advertising data types (from Zephyr bluetooth.h)
-pub const BT_DATA_FLAGS: u8 = 0x01;
-pub const BT_DATA_NAME_COMPLETE: u8 = 0x09;
+        self.client.send_command(packet.as_mut_slice()).await
+    }
 
-/// BLE advertising flags
-pub const BT_LE_AD_GENERAL: u8 = 0x02;
-pub const BT_LE_AD_NO_BREDR: u8 = 0x04;
+    /// Update advertising and scan response data
+    ///
+    /// Replaces the data of the running advertiser without restarting it, so
+    /// e.g. a counter in the payload can change without interrupting
+    /// advertising.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let ad = [BtData::flags(&[BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR])];
+    /// ble.bt_le_adv_update_data(&ad, &[]).await?;
+    /// ```
+    pub async fn bt_le_adv_update_data<'a>(
+        &mut self,
+        ad: &[BtData<'a>],
+        sd: &[BtData<'a>],
+    ) -> Result<i32, RpcError> {
+        let mut packet = encode_bt_le_adv_update_data::<256>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            ad,
+            sd,
+        )?;
 
-// ============================================================================
-// Data Structures
-// ============================================================================
+        self.client.send_command(packet.as_mut_slice()).await
+    }
 
-/// BLE advertising parameters
-///
-/// Corresponds to `bt_le_adv_param` struct in Zephyr
-#[derive(Debug, Clone, Copy)]
-pub struct BtLeAdvParam {
-    pub id: u8,
-    pub sid: u8,
-    pub secondary_max_skip: u8,
-    pub options: u32,
-    pub interval_min: u32,
-    pub interval_max: u32,
-    pub peer: Option<BtAddrLe>,
-}
+    /// Stop BLE advertising
+    ///
+    /// Returns the Zephyr error code reported by the remote.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_adv_stop().await?;
+    /// ```
+    pub async fn bt_le_adv_stop(&mut self) -> Result<i32, RpcError> {
+        let mut packet = self.command::<64>(BT_LE_ADV_STOP_RPC_CMD).cbor_null()?;
 
-impl BtLeAdvParam {
-    /// Create default connectable advertising parameters
-    pub fn connectable() -> Self {
-        Self {
-            id: 0,
-            sid: 0,
-            secondary_max_skip: 0,
-            options: BT_LE_ADV_OPT_CONNECTABLE,
-            interval_min: 160, // 100ms in 0.625ms units
-            interval_max: 240, // 150ms in 0.625ms units
-            peer: None,
-        }
+        self.client.send_command(packet.as_mut_slice()).await
     }
-}
 
-/// BLE advertising data
-///
-/// Corresponds to `bt_data` struct in Zephyr
-#[derive(Debug, Clone)]
-pub struct BtData<'a> {
-    pub data_type: u8,
-    pub data: &'a [u8],
-}
+    /// Create an extended advertising set
+    ///
+    /// The set is not advertising yet; start it with `bt_le_ext_adv_start`.
+    /// A negative error code from the remote is returned as `RpcError::Remote`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let set = ble.bt_le_ext_adv_create(&BtLeAdvParam::connectable()).await?;
+    /// ble.bt_le_ext_adv_start(set).await?;
+    /// ```
+    pub async fn bt_le_ext_adv_create(
+        &mut self,
+        param: &BtLeAdvParam,
+    ) -> Result<AdvSetHandle, RpcError> {
+        let scratchpad_size = match param.peer {
+            Some(_) => align_to_4(BT_ADDR_LE_SIZE),
+            None => 0,
+        };
+        let builder = self
+            .command::<64>(BT_LE_EXT_ADV_CREATE_RPC_CMD)
+            .cbor_uint(scratchpad_size as u64)?;
+        let mut packet = encode_bt_le_adv_param(builder, param)?.cbor_null()?;
 
-impl<'a> BtData<'a> {
-    /// Create BLE flags advertising data
-    pub fn flags(flags: &'a [u8]) -> Self {
-        Self {
-            data_type: BT_DATA_FLAGS,
-            data: flags,
-        }
+        self.client
+            .send_command_with(packet.as_mut_slice(), decode_ext_adv_create_response)
+            .await
     }
 
-    /// Create complete local name advertising data
-    pub fn name_complete(name: &'a [u8]) -> Self {
-        Self {
-            data_type: BT_DATA_NAME_COMPLETE,
-            data: name,
-        }
+    /// Start advertising with an extended advertising set
+    ///
+    /// Returns the Zephyr error code reported by the remote.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_ext_adv_start(set).await?;
+    /// ```
+    pub async fn bt_le_ext_adv_start(&mut self, set: AdvSetHandle) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_EXT_ADV_START_RPC_CMD)
+            .cbor_uint(set.0 as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
     }
-}
+
+    /// Stop advertising with an extended advertising set
+    ///
+    /// The set stays allocated and can be started again.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_ext_adv_stop(set).await?;
+    /// ```
+    pub async fn bt_le_ext_adv_stop(&mut self, set: AdvSetHandle) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_EXT_ADV_STOP_RPC_CMD)
+            .cbor_uint(set.0 as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Start BLE scanning
+    ///
+    /// # Example
+    /// ```ignore
+    /// let param = BtLeScanParam::active();
+    /// ble.bt_le_scan_start(&param).await?;
+    /// ```
+    pub async fn bt_le_scan_start(&mut self, param: &BtLeScanParam) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_SCAN_START_RPC_CMD)
+            .cbor_uint(param.scan_type as u64)?
+            .cbor_uint(param.options as u64)?
+            .cbor_uint(param.interval as u64)?
+            .cbor_uint(param.window as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Stop BLE scanning
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_scan_stop().await?;
+    /// ```
+    pub async fn bt_le_scan_stop(&mut self) -> Result<i32, RpcError> {
+        let mut packet = self.command::<64>(BT_LE_SCAN_STOP_RPC_CMD).cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Initiate a connection to a peripheral
+    ///
+    /// Returns the handle of the new connection. A non-zero error code from the
+    /// remote is reported as `RpcError::Remote`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let conn = ble
+    ///     .bt_conn_le_create(&peer, &BtConnLeCreateParam::create_conn(), &conn_param)
+    ///     .await?;
+    /// ```
+    pub async fn bt_conn_le_create(
+        &mut self,
+        peer: &BtAddrLe,
+        create_param: &BtConnLeCreateParam,
+        conn_param: &BtLeConnParam,
+    ) -> Result<ConnHandle, RpcError> {
+        let mut packet = encode_bt_conn_le_create::<64>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            peer,
+            create_param,
+            conn_param,
+        )?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), decode_conn_handle_response)
+            .await
+    }
+
+    /// Get information about a connection
+    ///
+    /// # Example
+    /// ```ignore
+    /// let info = ble.bt_conn_get_info(conn).await?;
+    /// // interval is in 1.25ms units
+    /// let interval_us = info.interval as u32 * 1250;
+    /// ```
+    pub async fn bt_conn_get_info(&mut self, conn: ConnHandle) -> Result<BtConnInfo, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = self
+            .command::<64>(BT_CONN_GET_INFO_RPC_CMD)
+            .cbor_uint(conn.0 as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), decode_conn_info_response)
+            .await
+    }
+
+    /// Get the local out-of-band data for LE Secure Connections pairing
+    ///
+    /// `id` is the identity to get the OOB data for.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // Identity 0 is the default identity
+    /// let oob = ble.bt_le_oob_get_local(0).await?;
+    /// // Hand oob.rand and oob.confirm to the peer, e.g. over NFC
+    /// ```
+    pub async fn bt_le_oob_get_local(&mut self, id: u8) -> Result<BtLeOob, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_OOB_GET_LOCAL_RPC_CMD)
+            .cbor_uint(id as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), decode_oob_get_local_response)
+            .await
+    }
+
+    /// Update the parameters of an established connection
+    ///
+    /// # Example
+    /// ```ignore
+    /// let param = BtLeConnParam {
+    ///     latency: 4,
+    ///     ..BtLeConnParam::default_le_1m()
+    /// };
+    /// ble.bt_le_conn_param_update(conn, &param).await?;
+    /// ```
+    pub async fn bt_le_conn_param_update(
+        &mut self,
+        conn: ConnHandle,
+        param: &BtLeConnParam,
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = encode_bt_le_conn_param_update::<64>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            conn,
+            param,
+        )?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Disconnect a connection
+    ///
+    /// `reason` is the HCI error code reported to the peer, typically
+    /// `BT_HCI_ERR_REMOTE_USER_TERM_CONN`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_conn_disconnect(conn, BT_HCI_ERR_REMOTE_USER_TERM_CONN).await?;
+    /// ```
+    pub async fn bt_conn_disconnect(
+        &mut self,
+        conn: ConnHandle,
+        reason: u8,
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = self
+            .command::<64>(BT_CONN_DISCONNECT_RPC_CMD)
+            .cbor_uint(conn.0 as u64)?
+            .cbor_uint(reason as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Request a PHY change for a connection
+    ///
+    /// `pref_tx` and `pref_rx` are bitmasks of acceptable `BT_GAP_LE_PHY_*`
+    /// values; the controller picks one of them. The returned code only reports
+    /// whether the procedure started; the PHY changes once it completes.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_conn_le_phy_update(conn, BT_GAP_LE_PHY_2M, BT_GAP_LE_PHY_2M).await?;
+    /// ```
+    pub async fn bt_conn_le_phy_update(
+        &mut self,
+        conn: ConnHandle,
+        pref_tx: u8,
+        pref_rx: u8,
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = self
+            .command::<64>(BT_CONN_LE_PHY_UPDATE_RPC_CMD)
+            .cbor_uint(conn.0 as u64)?
+            .cbor_uint(pref_tx as u64)?
+            .cbor_uint(pref_rx as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Request new data length parameters for a connection
+    ///
+    /// `tx_max_len` is the maximum payload in octets (27-251) and
+    /// `tx_max_time` the maximum transmission time in microseconds (328-17040)
+    /// of packets sent on the connection.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_conn_le_data_len_update(conn, 251, 2120).await?;
+    /// ```
+    pub async fn bt_conn_le_data_len_update(
+        &mut self,
+        conn: ConnHandle,
+        tx_max_len: u16,
+        tx_max_time: u16,
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = self
+            .command::<64>(BT_CONN_LE_DATA_LEN_UPDATE_RPC_CMD)
+            .cbor_uint(conn.0 as u64)?
+            .cbor_uint(tx_max_len as u64)?
+            .cbor_uint(tx_max_time as u64)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Add a device to the filter accept list
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_filter_accept_list_add(&peer).await?;
+    /// ```
+    pub async fn bt_le_filter_accept_list_add(&mut self, addr: &BtAddrLe) -> Result<i32, RpcError> {
+        let packet = self.command::<64>(BT_LE_FILTER_ACCEPT_LIST_ADD_RPC_CMD);
+        let mut packet = encode_bt_addr_le(packet, addr)?.cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Remove a device from the filter accept list
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_filter_accept_list_remove(&peer).await?;
+    /// ```
+    pub async fn bt_le_filter_accept_list_remove(
+        &mut self,
+        addr: &BtAddrLe,
+    ) -> Result<i32, RpcError> {
+        let packet = self.command::<64>(BT_LE_FILTER_ACCEPT_LIST_REMOVE_RPC_CMD);
+        let mut packet = encode_bt_addr_le(packet, addr)?.cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Remove all devices from the filter accept list
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_le_filter_accept_list_clear().await?;
+    /// ```
+    pub async fn bt_le_filter_accept_list_clear(&mut self) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_FILTER_ACCEPT_LIST_CLEAR_RPC_CMD)
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Set the channel map used for connections and advertising
+    ///
+    /// `chan_map` is the 37-bit data channel bit mask, channel 0 in the least
+    /// significant bit of the first byte; the upper 3 bits of the last byte
+    /// must be zero. Channels cannot all be disabled.
+    ///
+    /// Returns the Zephyr error code reported by the remote.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // Avoid channels 0-7, which overlap with a Wi-Fi network
+    /// ble.bt_le_set_chan_map(&[0x00, 0xFF, 0xFF, 0xFF, 0x1F]).await?;
+    /// ```
+    pub async fn bt_le_set_chan_map(&mut self, chan_map: &[u8; 5]) -> Result<i32, RpcError> {
+        let mut packet = self
+            .command::<64>(BT_LE_SET_CHAN_MAP_RPC_CMD)
+            .cbor_bytes(chan_map)?
+            .cbor_null()?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Create a new identity
+    ///
+    /// `addr` is the identity address to use, or `None` to let the controller
+    /// generate one. `irk` is the identity resolving key, or `None` to have one
+    /// generated. Returns the index of the new identity.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let id = ble.bt_id_create(None, None).await?;
+    /// ```
+    pub async fn bt_id_create(
+        &mut self,
+        addr: Option<&BtAddrLe>,
+        irk: Option<&[u8; 16]>,
+    ) -> Result<u8, RpcError> {
+        let mut packet = self.command::<64>(BT_ID_CREATE_RPC_CMD);
+        packet = match addr {
+            Some(addr) => encode_bt_addr_le(packet, addr)?,
+            None => packet.cbor_null()?,
+        };
+        packet = match irk {
+            Some(irk) => packet.cbor_bytes(irk)?,
+            None => packet.cbor_null()?,
+        };
+        let mut packet = packet.cbor_null()?;
+
+        // Non-negative results are the identity index, negative ones an errno
+        let id = self.client.send_command(packet.as_mut_slice()).await?;
+        if id < 0 {
+            return Err(RpcError::Remote(id));
+        }
+        u8::try_from(id).map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Send a GATT notification
+    ///
+    /// Notifies the peer on `conn` of a new value `data` for the attribute at
+    /// `attr_handle`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_gatt_notify(conn, 0x0012, &temperature.to_le_bytes()).await?;
+    /// ```
+    pub async fn bt_gatt_notify(
+        &mut self,
+        conn: ConnHandle,
+        attr_handle: u16,
+        data: &[u8],
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = encode_bt_gatt_notify::<256>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            conn,
+            attr_handle,
+            data,
+        )?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Check whether Bluetooth is enabled and ready for use
+    ///
+    /// # Example
+    /// ```ignore
+    /// if !ble.bt_is_ready().await? {
+    ///     ble.bt_enable().await?;
+    /// }
+    /// ```
+    pub async fn bt_is_ready(&mut self) -> Result<bool, RpcError> {
+        let mut packet = self.command::<64>(BT_IS_READY_RPC_CMD).cbor_null()?;
+
+        self.client.send_command_bool(packet.as_mut_slice()).await
+    }
+
+    /// Release a connection reference
+    ///
+    /// Every handle returned by `bt_conn_le_create` holds a reference on the
+    /// remote that must be released once the connection is no longer needed.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_conn_unref(conn).await?;
+    /// ```
+    pub async fn bt_conn_unref(&mut self, conn: ConnHandle) -> Result<(), RpcError> {
+        let mut packet = self
+            .command::<64>(BT_CONN_UNREF_RPC_CMD)
+            .cbor_uint(conn.0 as u64)?
+            .cbor_null()?;
+
+        self.client.send_command_void(packet.as_mut_slice()).await
+    }
+
+    /// Register a GATT service
+    ///
+    /// # Example
+    /// ```ignore
+    /// let battery_level = GattCharacteristic {
+    ///     uuid: BtUuid::U16(0x2A19),
+    ///     properties: BT_GATT_CHRC_READ | BT_GATT_CHRC_NOTIFY,
+    ///     permissions: BT_GATT_PERM_READ,
+    /// };
+    /// let svc = GattService {
+    ///     uuid: BtUuid::U16(0x180F),
+    ///     characteristics: &[battery_level],
+    /// };
+    /// ble.bt_gatt_service_register(&svc).await?;
+    /// ```
+    pub async fn bt_gatt_service_register(
+        &mut self,
+        svc: &GattService<'_>,
+    ) -> Result<i32, RpcError> {
+        let mut packet = encode_bt_gatt_service_register::<256>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            svc,
+        )?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Write an attribute value on a GATT server
+    ///
+    /// `data` is written to the attribute at `handle` starting at `offset`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// ble.bt_gatt_write(conn, 0x0012, 0, &[0x01, 0x00]).await?;
+    /// ```
+    pub async fn bt_gatt_write(
+        &mut self,
+        conn: ConnHandle,
+        handle: u16,
+        offset: u16,
+        data: &[u8],
+    ) -> Result<i32, RpcError> {
+        self.client.check_conn(conn)?;
+
+        let mut packet = encode_bt_gatt_write::<256>(
+            self.client.context_id(),
+            self.client.bt_rpc_group_id(),
+            self.client.bt_rpc_group_id(),
+            conn,
+            handle,
+            offset,
+            data,
+        )?;
+
+        self.client.send_command(packet.as_mut_slice()).await
+    }
+
+    /// Read an attribute value from a GATT server
+    ///
+    /// Reads up to `out.len()` bytes of the attribute at `handle` and returns
+    /// the part of `out` that was filled.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut buf = [0u8; 20];
+    /// let value = ble.bt_gatt_read(conn, 0x0012, &mut buf).await?;
+    /// ```
+    pub async fn bt_gatt_read<'b>(
+        &mut self,
+        conn: ConnHandle,
+        handle: u16,
+        out: &'b mut [u8],
+    ) -> Result<&'b [u8], RpcError> {
+        self.client.check_conn(conn)?;
+
+        // The remote reads into a scratchpad buffer of the requested length
+        let scratchpad_size = align_to_4(out.len());
+
+        let mut packet = self
+            .command::<64>(BT_GATT_READ_RPC_CMD)
+            .cbor_uint(scratchpad_size as u64)?
+            .cbor_uint(conn.0 as u64)?
+            .cbor_uint(handle as u64)?
+            .cbor_uint(out.len() as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), |payload| {
+                decode_gatt_read_response(payload, out)
+            })
+            .await
+    }
+
+    /// Read an attribute value longer than a single ATT response
+    ///
+    /// Issues reads at increasing offsets, each for at most `mtu - 1` bytes
+    /// (the payload of an ATT Read Blob response), and assembles them in
+    /// `out`. The value is complete once a read returns fewer bytes than
+    /// requested, so `mtu` must be the ATT MTU negotiated for `conn`.
+    ///
+    /// Returns `RpcError::ResponseTooLarge` if the value does not fit in `out`;
+    /// `needed` is then a lower bound, as the rest of the value is not read.
+    /// Read offsets are 16-bit, so an `out` longer than `u16::MAX` bytes fails
+    /// with `RpcError::InvalidArgument` before anything is sent.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut buf = [0u8; 512];
+    /// let value = ble.bt_gatt_read_long(conn, 0x0012, 23, &mut buf).await?;
+    /// ```
+    pub async fn bt_gatt_read_long<'b>(
+        &mut self,
+        conn: ConnHandle,
+        handle: u16,
+        mtu: u16,
+        out: &'b mut [u8],
+    ) -> Result<&'b [u8], RpcError> {
+        self.client.check_conn(conn)?;
+        if out.len() > usize::from(u16::MAX) {
+            return Err(RpcError::InvalidArgument);
+        }
+
+        let max_chunk = usize::from(mtu.saturating_sub(1)).max(1);
+        let mut filled = 0;
+        while filled < out.len() {
+            let requested = max_chunk.min(out.len() - filled);
+            let chunk = &mut out[filled..filled + requested];
+            let read = self.gatt_read_at(conn, handle, filled, chunk).await?;
+            filled += read;
+            if read < requested {
+                return Ok(&out[..filled]);
+            }
+        }
+
+        // `out` is full; probe for one more byte to tell an exact fit from truncation
+        let mut probe = [0u8; 1];
+        match self.gatt_read_at(conn, handle, filled, &mut probe).await? {
+            0 => Ok(out),
+            extra => Err(RpcError::ResponseTooLarge {
+                needed: filled + extra,
+            }),
+        }
+    }
+
+    /// Read up to `out.len()` bytes of an attribute starting at `offset`
+    ///
+    /// Returns the number of bytes read into `out`, or
+    /// `RpcError::InvalidArgument` if `offset` does not fit the 16-bit field.
+    async fn gatt_read_at(
+        &mut self,
+        conn: ConnHandle,
+        handle: u16,
+        offset: usize,
+        out: &mut [u8],
+    ) -> Result<usize, RpcError> {
+        let offset = u16::try_from(offset).map_err(|_| RpcError::InvalidArgument)?;
+        let scratchpad_size = align_to_4(out.len());
+
+        let mut packet = self
+            .command::<64>(BT_GATT_READ_OFFSET_RPC_CMD)
+            .cbor_uint(scratchpad_size as u64)?
+            .cbor_uint(conn.0 as u64)?
+            .cbor_uint(handle as u64)?
+            .cbor_uint(offset as u64)?
+            .cbor_uint(out.len() as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), |payload| {
+                decode_gatt_read_response(payload, out).map(<[u8]>::len)
+            })
+            .await
+    }
+
+    /// Fill `out` with random bytes from the remote's RNG
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut nonce = [0u8; 16];
+    /// ble.bt_rand(&mut nonce).await?;
+    /// ```
+    pub async fn bt_rand(&mut self, out: &mut [u8]) -> Result<(), RpcError> {
+        let mut packet = self
+            .command::<64>(BT_RAND_RPC_CMD)
+            .cbor_uint(out.len() as u64)?
+            .cbor_null()?;
+
+        self.client
+            .send_command_with(packet.as_mut_slice(), |payload| {
+                decode_rand_response(payload, out)
+            })
+            .await
+    }
+
+    /// Get the version string of the remote's firmware
+    ///
+    /// Sent in the `rpc_utils` group, so it works before Bluetooth is enabled
+    /// and makes a quick connectivity check right after the handshake. The
+    /// string is copied into `out`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut buf = [0u8; 64];
+    /// let version = ble.rpc_utils_get_version(&mut buf).await?;
+    /// ```
+    pub async fn rpc_utils_get_version<'b>(
+        &mut self,
+        out: &'b mut [u8],
+    ) -> Result<&'b str, RpcError> {
+        let mut packet = self
+            .client
+            .command_in_group::<64>(
+                self.client.rpc_utils_group_id(),
+                RPC_UTILS_GET_VERSION_RPC_CMD,
+            )
+            .cbor_null()?;
+
+        self.client
+            .send_command_str(packet.as_mut_slice(), out)
+            .await
+    }
+
+    /// Wait for the next event from the remote
+    ///
+    /// The event packet is received into `buf` and acknowledged before it is
+    /// decoded, so borrowed fields of the returned event point into `buf`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut buf = [0u8; 256];
+    /// if let Event::ScanRecv { addr, rssi, data, .. } = ble.next_event(&mut buf).await? {
+    ///     // ...
+    /// }
+    /// ```
+    pub async fn next_event<'b>(&mut self, buf: &'b mut [u8]) -> Result<Event<'b>, RpcError> {
+        let (header, len) = self.client.receive_event(buf).await?;
+        let buf: &'b [u8] = buf;
+        let event = decode_event(header.command_id, &buf[HEADER_LEN..len])?;
+        self.client.track_conn(&event);
+        Ok(event)
+    }
+
+    /// Run a command described by a `BleCommand`
+    ///
+    /// Dispatches to the dedicated method for the command, so the packet and
+    /// result are the same as calling it directly. Useful when commands are
+    /// queued or generated as data.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let queue = [BleCommand::Enable, BleCommand::AdvStop];
+    /// for cmd in queue {
+    ///     ble.execute(cmd).await?;
+    /// }
+    /// ```
+    pub async fn execute(&mut self, cmd: BleCommand<'_>) -> Result<CommandResult, RpcError> {
+        let code = match cmd {
+            BleCommand::Enable => self.bt_enable().await?,
+            BleCommand::Disable => self.bt_disable().await?,
+            BleCommand::SetName { name } => self.bt_set_name(name).await?,
+            BleCommand::AdvStart { param, ad, sd } => self.bt_le_adv_start(param, ad, sd).await?,
+            BleCommand::AdvStop => self.bt_le_adv_stop().await?,
+            BleCommand::ScanStart { param } => self.bt_le_scan_start(param).await?,
+            BleCommand::ScanStop => self.bt_le_scan_stop().await?,
+            BleCommand::ConnCreate {
+                peer,
+                create_param,
+                conn_param,
+            } => {
+                let conn = self
+                    .bt_conn_le_create(peer, create_param, conn_param)
+                    .await?;
+                return Ok(CommandResult::Conn(conn));
+            }
+            BleCommand::Disconnect { conn, reason } => {
+                self.bt_conn_disconnect(conn, reason).await?
+            }
+        };
+        Ok(CommandResult::Code(code))
+    }
+
+    /// Start a bt_rpc command packet for `cmd_id`
+    fn command<const N: usize>(&self, cmd_id: u8) -> PacketBuilder<N> {
+        self.client
+            .command_in_group(self.client.bt_rpc_group_id(), cmd_id)
+    }
+}
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// BLE advertising options
+pub const BT_LE_ADV_OPT_CONNECTABLE: BtLeAdvOpt = BtLeAdvOpt::CONNECTABLE;
+pub const BT_LE_ADV_OPT_ONE_TIME: BtLeAdvOpt = BtLeAdvOpt::ONE_TIME;
+
+/// BLE scan types
+pub const BT_LE_SCAN_TYPE_PASSIVE: u8 = 0x00;
+pub const BT_LE_SCAN_TYPE_ACTIVE: u8 = 0x01;
+
+/// BLE scan options
+pub const BT_LE_SCAN_OPT_NONE: u32 = 0x00000000;
+pub const BT_LE_SCAN_OPT_FILTER_DUPLICATE: u32 = 0x00000001;
+
+/// BLE address types
+pub const BT_ADDR_LE_PUBLIC: u8 = 0x00;
+pub const BT_ADDR_LE_RANDOM: u8 = 0x01;
+pub const BT_ADDR_LE_PUBLIC_ID: u8 = 0x02;
+pub const BT_ADDR_LE_RANDOM_ID: u8 = 0x03;
+
+/// HCI error codes used as disconnect reasons
+pub const BT_HCI_ERR_REMOTE_USER_TERM_CONN: u8 = 0x13;
+
+/// LE PHY bitmasks for `bt_conn_le_phy_update`
+pub const BT_GAP_LE_PHY_1M: u8 = 0x01;
+pub const BT_GAP_LE_PHY_2M: u8 = 0x02;
+pub const BT_GAP_LE_PHY_CODED: u8 = 0x04;
+
+/// BLE advertising data types (from Zephyr bluetooth.h)
+pub const BT_DATA_FLAGS: u8 = 0x01;
+pub const BT_DATA_UUID16_ALL: u8 = 0x03;
+pub const BT_DATA_NAME_COMPLETE: u8 = 0x09;
+pub const BT_DATA_TX_POWER: u8 = 0x0A;
+pub const BT_DATA_GAP_APPEARANCE: u8 = 0x19;
+pub const BT_DATA_MANUFACTURER_DATA: u8 = 0xFF;
+
+/// BLE advertising flags
+pub const BT_LE_AD_GENERAL: u8 = 0x02;
+pub const BT_LE_AD_NO_BREDR: u8 = 0x04;
+
+/// GATT characteristic properties
+pub const BT_GATT_CHRC_READ: u8 = 0x02;
+pub const BT_GATT_CHRC_WRITE_WITHOUT_RESP: u8 = 0x04;
+pub const BT_GATT_CHRC_WRITE: u8 = 0x08;
+pub const BT_GATT_CHRC_NOTIFY: u8 = 0x10;
+pub const BT_GATT_CHRC_INDICATE: u8 = 0x20;
+
+/// GATT attribute permissions
+pub const BT_GATT_PERM_NONE: u8 = 0x00;
+pub const BT_GATT_PERM_READ: u8 = 0x01;
+pub const BT_GATT_PERM_WRITE: u8 = 0x02;
+
+// ============================================================================
+// Unit Conversions
+// ============================================================================
+
+/// Convert an advertising interval in milliseconds to 0.625ms units
+///
+/// Rounds down, e.g. `adv_interval_ms(100)` is 160.
+pub const fn adv_interval_ms(ms: u16) -> u32 {
+    ms as u32 * 8 / 5
+}
+
+/// Convert a scan interval in milliseconds to 0.625ms units
+///
+/// Rounds down. The result saturates above 40959ms, well beyond the 10.24s
+/// the controller accepts.
+pub const fn scan_interval_ms(ms: u16) -> u16 {
+    let units = ms as u32 * 8 / 5;
+    if units > u16::MAX as u32 {
+        u16::MAX
+    } else {
+        units as u16
+    }
+}
+
+/// Convert a scan window in milliseconds to 0.625ms units
+///
+/// Same as `scan_interval_ms`; the window must not exceed the interval.
+pub const fn scan_window_ms(ms: u16) -> u16 {
+    scan_interval_ms(ms)
+}
+
+/// Convert a connection interval in milliseconds to 1.25ms units
+///
+/// Takes an `f32` because valid intervals like 7.5ms are not whole
+/// milliseconds. Rounds down and saturates at the `u16` range.
+pub const fn conn_interval_ms(ms: f32) -> u16 {
+    (ms / 1.25) as u16
+}
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// BLE advertising options
+///
+/// Corresponds to the `BT_LE_ADV_OPT_*` flags in Zephyr. Options are combined
+/// with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BtLeAdvOpt(u32);
+
+impl BtLeAdvOpt {
+    pub const NONE: Self = Self(0);
+    pub const CONNECTABLE: Self = Self(1 << 0);
+    pub const ONE_TIME: Self = Self(1 << 1);
+    pub const USE_IDENTITY: Self = Self(1 << 2);
+    pub const SCANNABLE: Self = Self(1 << 9);
+    pub const EXT_ADV: Self = Self(1 << 10);
+
+    /// Raw option bits as sent on the wire
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Check whether all options in `other` are set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl From<u32> for BtLeAdvOpt {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<BtLeAdvOpt> for u32 {
+    fn from(opt: BtLeAdvOpt) -> Self {
+        opt.0
+    }
+}
+
+impl core::ops::BitOr for BtLeAdvOpt {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for BtLeAdvOpt {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl core::ops::BitAnd for BtLeAdvOpt {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// BLE advertising parameters
+///
+/// Corresponds to `bt_le_adv_param` struct in Zephyr
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BtLeAdvParam {
+    pub id: u8,
+    pub sid: u8,
+    pub secondary_max_skip: u8,
+    pub options: BtLeAdvOpt,
+    pub interval_min: u32,
+    pub interval_max: u32,
+    pub peer: Option<BtAddrLe>,
+}
+
+impl BtLeAdvParam {
+    /// Create default connectable advertising parameters
+    pub fn connectable() -> Self {
+        Self {
+            id: 0,
+            sid: 0,
+            secondary_max_skip: 0,
+            options: BT_LE_ADV_OPT_CONNECTABLE,
+            interval_min: adv_interval_ms(100),
+            interval_max: adv_interval_ms(150),
+            peer: None,
+        }
+    }
+
+    /// Start building advertising parameters from the `connectable()` defaults
+    ///
+    /// # Example
+    /// ```ignore
+    /// let param = BtLeAdvParam::builder()
+    ///     .interval_min(320)
+    ///     .interval_max(400)
+    ///     .build();
+    /// ```
+    pub fn builder() -> BtLeAdvParamBuilder {
+        BtLeAdvParamBuilder::new()
+    }
+}
+
+/// Builder for `BtLeAdvParam`
+///
+/// Fields that are not set keep the values from `BtLeAdvParam::connectable()`.
+#[derive(Debug, Clone, Copy)]
+pub struct BtLeAdvParamBuilder {
+    param: BtLeAdvParam,
+}
+
+impl BtLeAdvParamBuilder {
+    pub fn new() -> Self {
+        Self {
+            param: BtLeAdvParam::connectable(),
+        }
+    }
+
+    /// Set the local identity to advertise with
+    pub fn id(mut self, id: u8) -> Self {
+        self.param.id = id;
+        self
+    }
+
+    /// Set the minimum advertising interval in 0.625ms units, see `adv_interval_ms`
+    pub fn interval_min(mut self, interval_min: u32) -> Self {
+        self.param.interval_min = interval_min;
+        self
+    }
+
+    /// Set the maximum advertising interval in 0.625ms units, see `adv_interval_ms`
+    pub fn interval_max(mut self, interval_max: u32) -> Self {
+        self.param.interval_max = interval_max;
+        self
+    }
+
+    /// Set the advertising options
+    pub fn options(mut self, options: impl Into<BtLeAdvOpt>) -> Self {
+        self.param.options = options.into();
+        self
+    }
+
+    /// Set the peer address for directed advertising
+    pub fn peer(mut self, peer: BtAddrLe) -> Self {
+        self.param.peer = Some(peer);
+        self
+    }
+
+    pub fn build(self) -> BtLeAdvParam {
+        self.param
+    }
+}
+
+impl Default for BtLeAdvParamBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// BLE scan parameters
+///
+/// Corresponds to `bt_le_scan_param` struct in Zephyr
+#[derive(Debug, Clone, Copy)]
+pub struct BtLeScanParam {
+    pub scan_type: u8,
+    pub options: u32,
+    pub interval: u16,
+    pub window: u16,
+}
+
+impl BtLeScanParam {
+    /// Create passive scan parameters with duplicate filtering
+    pub fn passive() -> Self {
+        Self {
+            scan_type: BT_LE_SCAN_TYPE_PASSIVE,
+            options: BT_LE_SCAN_OPT_FILTER_DUPLICATE,
+            interval: scan_interval_ms(60),
+            window: scan_window_ms(30),
+        }
+    }
+
+    /// Create active scan parameters with duplicate filtering
+    pub fn active() -> Self {
+        Self {
+            scan_type: BT_LE_SCAN_TYPE_ACTIVE,
+            ..Self::passive()
+        }
+    }
+}
+
+/// BLE advertising data
+///
+/// Corresponds to `bt_data` struct in Zephyr
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BtData<'a> {
+    pub data_type: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a> BtData<'a> {
+    /// Create BLE flags advertising data
+    pub fn flags(flags: &'a [u8]) -> Self {
+        Self {
+            data_type: BT_DATA_FLAGS,
+            data: flags,
+        }
+    }
+
+    /// Create complete local name advertising data
+    pub fn name_complete(name: &'a [u8]) -> Self {
+        Self {
+            data_type: BT_DATA_NAME_COMPLETE,
+            data: name,
+        }
+    }
+
+    /// Create manufacturer specific advertising data
+    ///
+    /// The first two bytes of `data` are the company identifier (little-endian).
+    pub fn manufacturer_data(data: &'a [u8]) -> Self {
+        Self {
+            data_type: BT_DATA_MANUFACTURER_DATA,
+            data,
+        }
+    }
+
+    /// Create complete list of 16-bit service UUIDs advertising data holding
+    /// `uuid`
+    ///
+    /// The little-endian bytes are built here, so the element is returned as
+    /// a `BtDataBuf`; borrow it with `as_bt_data`. Use
+    /// `BtDataBuf::service_uuid_list` to advertise several services. Returns
+    /// `CborError::EncodingError` if `uuid` is not 16-bit.
+    pub fn service_uuid16(uuid: BtUuid) -> Result<BtDataBuf<2>, CborError> {
+        BtDataBuf::service_uuid_list([uuid])
+    }
+
+    /// Create GAP appearance advertising data
+    ///
+    /// `value` is the 2-byte appearance value in little-endian order.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // Generic heart rate sensor (0x0340)
+    /// let appearance = 0x0340u16.to_le_bytes();
+    /// let ad = [
+    ///     BtData::flags(&[BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR]),
+    ///     BtData::appearance(&appearance),
+    ///     BtData::name_complete(b"Sensor"),
+    /// ];
+    /// ble.bt_le_adv_start(&BtLeAdvParam::connectable(), &ad, &[]).await?;
+    /// ```
+    pub fn appearance(value: &'a [u8]) -> Self {
+        Self {
+            data_type: BT_DATA_GAP_APPEARANCE,
+            data: value,
+        }
+    }
+
+    /// Create TX power level advertising data
+    ///
+    /// `level` is a single signed byte in dBm.
+    pub fn tx_power(level: &'a [u8]) -> Self {
+        Self {
+            data_type: BT_DATA_TX_POWER,
+            data: level,
+        }
+    }
+}
+
+/// Advertising data element that owns its bytes
+///
+/// Built from native integers by constructors that handle the little-endian
+/// conversion, then borrowed as a `BtData` for advertising.
+///
+/// # Example
+/// ```ignore
+/// let appearance = BtDataBuf::appearance_u16(0x0340);
+/// let uuids = BtDataBuf::<4>::service_uuid_list([BtUuid::U16(0x180F), BtUuid::U16(0x180A)])?;
+/// let ad = [appearance.as_bt_data(), uuids.as_bt_data()];
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BtDataBuf<const N: usize> {
+    data_type: u8,
+    buf: [u8; N],
+    len: usize,
+}
+
+impl BtDataBuf<2> {
+    /// Create GAP appearance advertising data from its numeric value
+    pub fn appearance_u16(value: u16) -> Self {
+        Self {
+            data_type: BT_DATA_GAP_APPEARANCE,
+            buf: value.to_le_bytes(),
+            len: 2,
+        }
+    }
+}
+
+impl<const N: usize> BtDataBuf<N> {
+    /// Create complete list of 16-bit service UUIDs advertising data
+    ///
+    /// Returns `CborError::BufferTooSmall` if the UUIDs need more than `N`
+    /// bytes (two per UUID).
+    pub fn service_uuid16_list(uuids: &[u16]) -> Result<Self, CborError> {
+        Self::service_uuid_list(uuids.iter().map(|&uuid| BtUuid::U16(uuid)))
+    }
+
+    /// Create complete list of 16-bit service UUIDs advertising data from
+    /// `BtUuid`s
+    ///
+    /// Each UUID is written little-endian. Returns `CborError::EncodingError`
+    /// if any UUID is 32- or 128-bit, since those do not belong in a 16-bit
+    /// list, and `CborError::BufferTooSmall` if the UUIDs need more than `N`
+    /// bytes.
+    pub fn service_uuid_list(uuids: impl IntoIterator<Item = BtUuid>) -> Result<Self, CborError> {
+        let mut buf = [0u8; N];
+        let mut len = 0;
+        for uuid in uuids {
+            let BtUuid::U16(value) = uuid else {
+                return Err(CborError::EncodingError);
+            };
+            let chunk = buf.get_mut(len..len + 2).ok_or(CborError::BufferTooSmall)?;
+            chunk.copy_from_slice(&value.to_le_bytes());
+            len += 2;
+        }
+
+        Ok(Self {
+            data_type: BT_DATA_UUID16_ALL,
+            buf,
+            len,
+        })
+    }
+
+    /// Borrow as a `BtData` element
+    pub fn as_bt_data(&self) -> BtData<'_> {
+        BtData {
+            data_type: self.data_type,
+            data: &self.buf[..self.len],
+        }
+    }
+}
+
+/// Iterator over the AD structures of received advertising data
+///
+/// Each structure is a length byte, a type byte and `length - 1` bytes of
+/// data. Iteration stops at a zero length, which marks the end of the
+/// significant part, or at a length that runs past the end of the buffer.
+///
+/// # Example
+/// ```ignore
+/// if let Event::ScanRecv { data, .. } = ble.next_event(&mut buf).await? {
+///     for ad in AdStructIter::new(data) {
+///         if ad.data_type == BT_DATA_NAME_COMPLETE {
+///             defmt::info!("name: {=[u8]:a}", ad.data);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdStructIter<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> AdStructIter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { remaining: data }
+    }
+}
+
+impl<'a> Iterator for AdStructIter<'a> {
+    type Item = BtData<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.remaining.split_first()?;
+        let Some((element, rest)) = rest.split_at_checked(len as usize) else {
+            self.remaining = &[];
+            return None;
+        };
+        let Some((&data_type, data)) = element.split_first() else {
+            self.remaining = &[];
+            return None;
+        };
+
+        self.remaining = rest;
+        Some(BtData { data_type, data })
+    }
+}
 
 /// BLE address with type
+///
+/// `addr` is stored in little-endian order as it appears on the wire, i.e.
+/// reversed relative to the usual `AA:BB:CC:DD:EE:FF` notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BtAddrLe {
+    pub addr_type: u8,
+    pub addr: [u8; 6],
+}
+
+/// Error returned when parsing a `BtAddrLe` from a string fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddrParseError;
+
+impl core::fmt::Display for AddrParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid Bluetooth address")
+    }
+}
+
+impl core::str::FromStr for BtAddrLe {
+    type Err = AddrParseError;
+
+    /// Parse `"AA:BB:CC:DD:EE:FF"` with an optional `(public)`, `(random)`,
+    /// `(public-id)` or `(random-id)` suffix. Without a suffix the address is
+    /// public.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr_str, addr_type) = match s.split_once(' ') {
+            None => (s, BT_ADDR_LE_PUBLIC),
+            Some((addr_str, suffix)) => {
+                let addr_type = match suffix.trim() {
+                    "(public)" => BT_ADDR_LE_PUBLIC,
+                    "(random)" => BT_ADDR_LE_RANDOM,
+                    "(public-id)" => BT_ADDR_LE_PUBLIC_ID,
+                    "(random-id)" => BT_ADDR_LE_RANDOM_ID,
+                    _ => return Err(AddrParseError),
+                };
+                (addr_str, addr_type)
+            }
+        };
+
+        let mut addr = [0u8; 6];
+        let mut octets = addr_str.split(':');
+        // Most significant octet comes first in the string
+        for byte in addr.iter_mut().rev() {
+            let octet = octets.next().ok_or(AddrParseError)?;
+            if octet.len() != 2 {
+                return Err(AddrParseError);
+            }
+            *byte = u8::from_str_radix(octet, 16).map_err(|_| AddrParseError)?;
+        }
+        if octets.next().is_some() {
+            return Err(AddrParseError);
+        }
+
+        Ok(Self { addr_type, addr })
+    }
+}
+
+impl core::fmt::Display for BtAddrLe {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let a = &self.addr;
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            a[5], a[4], a[3], a[2], a[1], a[0]
+        )?;
+        match self.addr_type {
+            BT_ADDR_LE_PUBLIC => write!(f, " (public)"),
+            BT_ADDR_LE_RANDOM => write!(f, " (random)"),
+            BT_ADDR_LE_PUBLIC_ID => write!(f, " (public-id)"),
+            BT_ADDR_LE_RANDOM_ID => write!(f, " (random-id)"),
+            other => write!(f, " (0x{:02x})", other),
+        }
+    }
+}
+
+/// Connection creation parameters
+///
+/// Corresponds to `bt_conn_le_create_param` struct in Zephyr
+#[derive(Debug, Clone, Copy)]
+pub struct BtConnLeCreateParam {
+    pub options: u32,
+    pub interval: u16,
+    pub window: u16,
+    pub interval_coded: u16,
+    pub window_coded: u16,
+    pub timeout: u16,
+}
+
+impl BtConnLeCreateParam {
+    /// Create default connection parameters (`BT_CONN_LE_CREATE_CONN`)
+    pub fn create_conn() -> Self {
+        Self {
+            options: 0,
+            interval: scan_interval_ms(60),
+            window: scan_window_ms(60),
+            interval_coded: 0,
+            window_coded: 0,
+            timeout: 0,
+        }
+    }
+}
+
+/// LE connection parameters
+///
+/// Corresponds to `bt_le_conn_param` struct in Zephyr
+#[derive(Debug, Clone, Copy)]
+pub struct BtLeConnParam {
+    pub interval_min: u16,
+    pub interval_max: u16,
+    pub latency: u16,
+    pub timeout: u16,
+}
+
+impl BtLeConnParam {
+    /// Default connection parameters (`BT_LE_CONN_PARAM_DEFAULT`)
+    pub fn default_le_1m() -> Self {
+        Self {
+            interval_min: conn_interval_ms(30.0),
+            interval_max: conn_interval_ms(50.0),
+            latency: 0,
+            timeout: 400, // 4s in 10ms units
+        }
+    }
+}
+
+/// Information about an established connection
+///
+/// Corresponds to the LE part of the `bt_conn_info` struct in Zephyr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BtConnInfo {
+    /// `BT_CONN_ROLE_CENTRAL` (0) or `BT_CONN_ROLE_PERIPHERAL` (1)
+    pub role: u8,
+    /// Connection interval in 1.25ms units
+    pub interval: u16,
+    /// Peripheral latency in connection events
+    pub latency: u16,
+    /// Supervision timeout in 10ms units
+    pub timeout: u16,
+    /// Address of the peer
+    pub addr: BtAddrLe,
+}
+
+/// Local out-of-band data for LE Secure Connections pairing
+///
+/// Corresponds to `bt_le_oob` in Zephyr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BtLeOob {
+    /// LE address used for pairing
+    pub addr: BtAddrLe,
+    /// Random number
+    pub rand: [u8; 16],
+    /// Confirm value
+    pub confirm: [u8; 16],
+}
+
+/// Handle identifying a connection on the remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnHandle(pub u32);
+
+/// Handle identifying an extended advertising set on the remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AdvSetHandle(pub u32);
+
+/// Bluetooth UUID
+///
+/// Corresponds to `bt_uuid_16`, `bt_uuid_32` and `bt_uuid_128` in Zephyr.
+/// 128-bit UUIDs are stored in little-endian order as they appear on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BtUuid {
+    U16(u16),
+    U32(u32),
+    U128([u8; 16]),
+}
+
+/// Error returned when parsing a `BtUuid` from a string fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UuidParseError;
+
+impl core::fmt::Display for UuidParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid Bluetooth UUID")
+    }
+}
+
+impl core::str::FromStr for BtUuid {
+    type Err = UuidParseError;
+
+    /// Parse a short UUID of 4 or 8 hex digits, e.g. `"180f"` or `"0x180F"`,
+    /// or a 128-bit UUID in the dashed form
+    /// `"0000180f-0000-1000-8000-00805f9b34fb"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains('-') {
+            let digits = s
+                .strip_prefix("0x")
+                .or_else(|| s.strip_prefix("0X"))
+                .unwrap_or(s);
+            if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(UuidParseError);
+            }
+            return match digits.len() {
+                4 => Ok(Self::U16(
+                    u16::from_str_radix(digits, 16).map_err(|_| UuidParseError)?,
+                )),
+                8 => Ok(Self::U32(
+                    u32::from_str_radix(digits, 16).map_err(|_| UuidParseError)?,
+                )),
+                _ => Err(UuidParseError),
+            };
+        }
+
+        let mut uuid = [0u8; 16];
+        let mut bytes = uuid.iter_mut().rev();
+        for (group, len) in s.split('-').zip([8, 4, 4, 4, 12]) {
+            if group.len() != len || !group.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(UuidParseError);
+            }
+            // Most significant byte comes first in the string
+            for pair in group.as_bytes().chunks(2) {
+                let pair = core::str::from_utf8(pair).map_err(|_| UuidParseError)?;
+                *bytes.next().ok_or(UuidParseError)? =
+                    u8::from_str_radix(pair, 16).map_err(|_| UuidParseError)?;
+            }
+        }
+        if s.split('-').count() != 5 {
+            return Err(UuidParseError);
+        }
+
+        Ok(Self::U128(uuid))
+    }
+}
+
+/// GATT characteristic to register as part of a `GattService`
 #[derive(Debug, Clone, Copy)]
-pub struct BtAddrLe {
-    pub addr_type: u8,
-    pub addr: [u8; 6],
+pub struct GattCharacteristic {
+    pub uuid: BtUuid,
+    /// `BT_GATT_CHRC_*` properties
+    pub properties: u8,
+    /// `BT_GATT_PERM_*` permissions of the characteristic value
+    pub permissions: u8,
+}
+
+/// Primary GATT service and its characteristics
+///
+/// Registered as an attribute table of one primary service declaration
+/// followed by a declaration and a value attribute per characteristic.
+#[derive(Debug, Clone, Copy)]
+pub struct GattService<'a> {
+    pub uuid: BtUuid,
+    pub characteristics: &'a [GattCharacteristic],
+}
+
+/// A command for `Ble::execute`
+///
+/// Each variant carries the arguments of the `Ble` method of the same name.
+#[derive(Debug, Clone, Copy)]
+pub enum BleCommand<'a> {
+    /// `bt_enable`
+    Enable,
+    /// `bt_disable`
+    Disable,
+    /// `bt_set_name`
+    SetName { name: &'a str },
+    /// `bt_le_adv_start`
+    AdvStart {
+        param: &'a BtLeAdvParam,
+        ad: &'a [BtData<'a>],
+        sd: &'a [BtData<'a>],
+    },
+    /// `bt_le_adv_stop`
+    AdvStop,
+    /// `bt_le_scan_start`
+    ScanStart { param: &'a BtLeScanParam },
+    /// `bt_le_scan_stop`
+    ScanStop,
+    /// `bt_conn_le_create`
+    ConnCreate {
+        peer: &'a BtAddrLe,
+        create_param: &'a BtConnLeCreateParam,
+        conn_param: &'a BtLeConnParam,
+    },
+    /// `bt_conn_disconnect`
+    Disconnect { conn: ConnHandle, reason: u8 },
+}
+
+/// Result of a command run through `Ble::execute`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandResult {
+    /// Zephyr error code reported by the remote
+    Code(i32),
+    /// Connection created by `BleCommand::ConnCreate`
+    Conn(ConnHandle),
+}
+
+/// Asynchronous event reported by the remote
+#[derive(Debug, Clone)]
+pub enum Event<'b> {
+    /// Bluetooth finished initializing after `bt_enable`, or failed if `err`
+    /// is non-zero
+    Ready { err: i32 },
+    /// A connection was established, or failed to be if `err` is non-zero
+    Connected { conn: ConnHandle, err: u8 },
+    /// A connection was terminated with HCI `reason`
+    Disconnected { conn: ConnHandle, reason: u8 },
+    /// Advertising report received while scanning
+    ScanRecv {
+        addr: BtAddrLe,
+        rssi: i8,
+        adv_type: u8,
+        data: &'b [u8],
+    },
+    /// An event this crate has no decoder for
+    Unknown { cmd: u8, payload: &'b [u8] },
 }
 
 // ============================================================================
@@ -187,14 +2013,75 @@ pub struct BtAddrLe {
 // ============================================================================
 
 const BT_ENABLE_RPC_CMD: u8 = 0x00;
+const BT_DISABLE_RPC_CMD: u8 = 0x01;
+const BT_SET_NAME_RPC_CMD: u8 = 0x02;
+const BT_GET_NAME_OUT_RPC_CMD: u8 = 0x03;
 const BT_LE_ADV_START_RPC_CMD: u8 = 0x04;
+const BT_LE_ADV_STOP_RPC_CMD: u8 = 0x05;
+const BT_LE_SCAN_START_RPC_CMD: u8 = 0x06;
+const BT_LE_SCAN_STOP_RPC_CMD: u8 = 0x07;
+const BT_CONN_LE_CREATE_RPC_CMD: u8 = 0x08;
+const BT_CONN_DISCONNECT_RPC_CMD: u8 = 0x09;
+const BT_ID_CREATE_RPC_CMD: u8 = 0x0A;
+const BT_LE_ADV_UPDATE_DATA_RPC_CMD: u8 = 0x0B;
+const BT_RAND_RPC_CMD: u8 = 0x0C;
+const BT_GATT_NOTIFY_RPC_CMD: u8 = 0x0D;
+const BT_IS_READY_RPC_CMD: u8 = 0x0E;
+const BT_CONN_UNREF_RPC_CMD: u8 = 0x0F;
+const BT_LE_CONN_PARAM_UPDATE_RPC_CMD: u8 = 0x10;
+const BT_CONN_GET_INFO_RPC_CMD: u8 = 0x11;
+const BT_LE_ADV_START_RAW_RPC_CMD: u8 = 0x12;
+const BT_GATT_SERVICE_REGISTER_RPC_CMD: u8 = 0x13;
+const BT_GATT_WRITE_RPC_CMD: u8 = 0x14;
+const BT_GATT_READ_RPC_CMD: u8 = 0x15;
+const BT_LE_OOB_GET_LOCAL_RPC_CMD: u8 = 0x16;
+const BT_LE_FILTER_ACCEPT_LIST_ADD_RPC_CMD: u8 = 0x17;
+const BT_LE_FILTER_ACCEPT_LIST_REMOVE_RPC_CMD: u8 = 0x18;
+const BT_LE_FILTER_ACCEPT_LIST_CLEAR_RPC_CMD: u8 = 0x19;
+const BT_LE_SET_CHAN_MAP_RPC_CMD: u8 = 0x1A;
+const BT_GATT_READ_OFFSET_RPC_CMD: u8 = 0x1B;
+const BT_CONN_LE_PHY_UPDATE_RPC_CMD: u8 = 0x1C;
+const BT_CONN_LE_DATA_LEN_UPDATE_RPC_CMD: u8 = 0x1D;
+const BT_LE_EXT_ADV_CREATE_RPC_CMD: u8 = 0x1E;
+const BT_LE_EXT_ADV_START_RPC_CMD: u8 = 0x1F;
+const BT_LE_EXT_ADV_STOP_RPC_CMD: u8 = 0x20;
+
+// rpc_utils group commands are numbered separately
+const RPC_UTILS_GET_VERSION_RPC_CMD: u8 = 0x00;
+
+// ============================================================================
+// Event IDs
+// ============================================================================
+
+const BT_LE_SCAN_CB_RPC_EVT: u8 = 0x00;
+const BT_CONN_CB_CONNECTED_RPC_EVT: u8 = 0x01;
+const BT_CONN_CB_DISCONNECTED_RPC_EVT: u8 = 0x02;
+const BT_READY_CB_RPC_EVT: u8 = 0x03;
 
 // ============================================================================
 // Internal Encoding Functions
 // ============================================================================
 
+/// Smallest possible bt_le_adv_start packet
+///
+/// Header, scratchpad size, the six parameter fields and a null peer, two
+/// empty element counts and the terminator, each encoded in a single byte.
+/// Every parameter above 23 and every AD element adds to this, so size `N`
+/// for the largest payload actually sent.
+pub const BT_LE_ADV_START_MIN_LEN: usize = HEADER_LEN + 1 + 6 + 1 + 2 + 1;
+
 /// Encode bt_le_adv_start command
 ///
+/// `N` below `BT_LE_ADV_START_MIN_LEN` can never hold the packet and fails to
+/// compile:
+///
+/// ```compile_fail
+/// use nrf_rpc::ble::{encode_bt_le_adv_start, BtLeAdvParam};
+///
+/// let packet =
+///     encode_bt_le_adv_start::<8>(0x00, 0x00, 0x00, &BtLeAdvParam::connectable(), &[], &[]);
+/// ```
+///
 /// This is exposed for testing purposes.
 #[doc(hidden)]
 pub fn encode_bt_le_adv_start<const N: usize>(
@@ -205,6 +2092,13 @@ pub fn encode_bt_le_adv_start<const N: usize>(
     ad: &[BtData],
     sd: &[BtData],
 ) -> Result<PacketBuilder<N>, CborError> {
+    const {
+        assert!(
+            N >= BT_LE_ADV_START_MIN_LEN,
+            "buffer too small for bt_le_adv_start"
+        )
+    };
+
     let scratchpad_size = calculate_scratchpad_size(param, ad, sd);
 
     let mut builder = PacketBuilder::<N>::new()
@@ -217,18 +2111,96 @@ pub fn encode_bt_le_adv_start<const N: usize>(
         )
         .cbor_uint(scratchpad_size as u64)?;
 
-    // Encode bt_le_adv_param
-    builder = builder
+    builder = encode_bt_le_adv_param(builder, param)?;
+
+    encode_adv_data(builder, ad, sd)
+}
+
+/// Encode bt_le_adv_start command with pre-formatted AD and SD bytes
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_le_adv_start_raw<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    param: &BtLeAdvParam,
+    ad: &[u8],
+    sd: &[u8],
+) -> Result<PacketBuilder<N>, CborError> {
+    let mut scratchpad_size = align_to_4(ad.len()) + align_to_4(sd.len());
+    if param.peer.is_some() {
+        scratchpad_size += align_to_4(BT_ADDR_LE_SIZE);
+    }
+
+    let builder = PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_LE_ADV_START_RAW_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?;
+
+    encode_bt_le_adv_param(builder, param)?
+        .cbor_bytes(ad)?
+        .cbor_bytes(sd)?
+        .cbor_null()
+}
+
+/// Encode a bt_le_adv_param struct, including its optional peer address
+fn encode_bt_le_adv_param<const N: usize>(
+    builder: PacketBuilder<N>,
+    param: &BtLeAdvParam,
+) -> Result<PacketBuilder<N>, CborError> {
+    let builder = builder
         .cbor_uint(param.id as u64)?
         .cbor_uint(param.sid as u64)?
         .cbor_uint(param.secondary_max_skip as u64)?
-        .cbor_uint(param.options as u64)?
+        .cbor_uint(param.options.bits() as u64)?
         .cbor_uint(param.interval_min as u64)?
         .cbor_uint(param.interval_max as u64)?;
 
     // Encode peer address (null if None)
-    builder = builder.cbor_null()?;
+    match &param.peer {
+        Some(peer) => encode_bt_addr_le(builder, peer),
+        None => builder.cbor_null(),
+    }
+}
+
+/// Encode bt_le_adv_update_data command
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_le_adv_update_data<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    ad: &[BtData],
+    sd: &[BtData],
+) -> Result<PacketBuilder<N>, CborError> {
+    let scratchpad_size = scratchpad_for_bt_data(ad) + scratchpad_for_bt_data(sd);
 
+    let builder = PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_LE_ADV_UPDATE_DATA_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?;
+
+    encode_adv_data(builder, ad, sd)
+}
+
+/// Encode the ad and sd arrays followed by the terminator
+fn encode_adv_data<const N: usize>(
+    mut builder: PacketBuilder<N>,
+    ad: &[BtData],
+    sd: &[BtData],
+) -> Result<PacketBuilder<N>, CborError> {
     // Encode advertising data array
     builder = builder.cbor_uint(ad.len() as u64)?;
     for ad_item in ad {
@@ -241,12 +2213,401 @@ pub fn encode_bt_le_adv_start<const N: usize>(
         builder = encode_bt_data(builder, sd_item)?;
     }
 
+    // Terminator
+    builder.cbor_null()
+}
+
+/// Encode bt_conn_le_create command
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_conn_le_create<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    peer: &BtAddrLe,
+    create_param: &BtConnLeCreateParam,
+    conn_param: &BtLeConnParam,
+) -> Result<PacketBuilder<N>, CborError> {
+    let scratchpad_size = align_to_4(BT_ADDR_LE_SIZE);
+
+    let mut builder = PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_CONN_LE_CREATE_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?;
+
+    builder = encode_bt_addr_le(builder, peer)?;
+
+    // Encode bt_conn_le_create_param
+    builder = builder
+        .cbor_uint(create_param.options as u64)?
+        .cbor_uint(create_param.interval as u64)?
+        .cbor_uint(create_param.window as u64)?
+        .cbor_uint(create_param.interval_coded as u64)?
+        .cbor_uint(create_param.window_coded as u64)?
+        .cbor_uint(create_param.timeout as u64)?;
+
+    builder = encode_bt_le_conn_param(builder, conn_param)?;
+
     // Terminator
     builder = builder.cbor_null()?;
 
     Ok(builder)
 }
 
+/// Encode bt_le_conn_param_update command
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_le_conn_param_update<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    conn: ConnHandle,
+    param: &BtLeConnParam,
+) -> Result<PacketBuilder<N>, CborError> {
+    let builder = PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_LE_CONN_PARAM_UPDATE_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(conn.0 as u64)?;
+
+    encode_bt_le_conn_param(builder, param)?.cbor_null()
+}
+
+/// Encode a bt_le_conn_param struct in Zephyr field order
+fn encode_bt_le_conn_param<const N: usize>(
+    builder: PacketBuilder<N>,
+    param: &BtLeConnParam,
+) -> Result<PacketBuilder<N>, CborError> {
+    builder
+        .cbor_uint(param.interval_min as u64)?
+        .cbor_uint(param.interval_max as u64)?
+        .cbor_uint(param.latency as u64)?
+        .cbor_uint(param.timeout as u64)
+}
+
+/// Encode bt_gatt_notify command
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_gatt_notify<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    conn: ConnHandle,
+    attr_handle: u16,
+    data: &[u8],
+) -> Result<PacketBuilder<N>, CborError> {
+    // The remote decodes the value into the scratchpad
+    let scratchpad_size = align_to_4(data.len());
+
+    PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_GATT_NOTIFY_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?
+        .cbor_uint(conn.0 as u64)?
+        .cbor_uint(attr_handle as u64)?
+        .cbor_bytes(data)?
+        .cbor_null()
+}
+
+/// Encode bt_gatt_service_register command
+///
+/// Each attribute is encoded as its UUID, its permissions and its user data:
+/// - primary service declaration: the service UUID
+/// - characteristic declaration: the properties and the characteristic UUID
+/// - characteristic value: null
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_gatt_service_register<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    svc: &GattService,
+) -> Result<PacketBuilder<N>, CborError> {
+    let scratchpad_size = scratchpad_for_gatt_service(svc);
+    let attr_count = 1 + 2 * svc.characteristics.len();
+
+    let mut builder = PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_GATT_SERVICE_REGISTER_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?
+        .cbor_uint(attr_count as u64)?;
+
+    // Primary service declaration
+    builder = encode_bt_uuid(builder, &BtUuid::U16(BT_UUID_GATT_PRIMARY))?
+        .cbor_uint(BT_GATT_PERM_READ as u64)?;
+    builder = encode_bt_uuid(builder, &svc.uuid)?;
+
+    for chrc in svc.characteristics {
+        // Characteristic declaration
+        builder = encode_bt_uuid(builder, &BtUuid::U16(BT_UUID_GATT_CHRC))?
+            .cbor_uint(BT_GATT_PERM_READ as u64)?
+            .cbor_uint(chrc.properties as u64)?;
+        builder = encode_bt_uuid(builder, &chrc.uuid)?;
+
+        // Characteristic value
+        builder = encode_bt_uuid(builder, &chrc.uuid)?
+            .cbor_uint(chrc.permissions as u64)?
+            .cbor_null()?;
+    }
+
+    builder.cbor_null()
+}
+
+/// Encode bt_gatt_write command
+///
+/// This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn encode_bt_gatt_write<const N: usize>(
+    src_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+    conn: ConnHandle,
+    handle: u16,
+    offset: u16,
+    data: &[u8],
+) -> Result<PacketBuilder<N>, CborError> {
+    // The remote decodes the value into the scratchpad
+    let scratchpad_size = align_to_4(data.len());
+
+    PacketBuilder::<N>::new()
+        .command(
+            src_ctx_id,
+            BT_GATT_WRITE_RPC_CMD,
+            0xFF,
+            src_grp_id,
+            dst_grp_id,
+        )
+        .cbor_uint(scratchpad_size as u64)?
+        .cbor_uint(conn.0 as u64)?
+        .cbor_uint(handle as u64)?
+        .cbor_uint(offset as u64)?
+        .cbor_bytes(data)?
+        .cbor_null()
+}
+
+/// Decode a response carrying an error code followed by a connection handle
+fn decode_conn_handle_response(payload: &[u8]) -> Result<ConnHandle, RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let handle = decoder.decode_u64()?;
+    decoder.expect_null()?;
+    u32::try_from(handle)
+        .map(ConnHandle)
+        .map_err(|_| RpcError::InvalidResponse)
+}
+
+/// Decode a response carrying an error code followed by `bt_conn_info`
+///
+/// Fields follow Zephyr's struct order: role, peer address, then the
+/// interval, latency and timeout.
+fn decode_conn_info_response(payload: &[u8]) -> Result<BtConnInfo, RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let role = decoder.decode_u8()?;
+    let addr = decode_bt_addr_le_field(&mut decoder)?;
+    let interval = decoder.decode_u16()?;
+    let latency = decoder.decode_u16()?;
+    let timeout = decoder.decode_u16()?;
+    decoder.expect_null()?;
+
+    Ok(BtConnInfo {
+        role,
+        interval,
+        latency,
+        timeout,
+        addr,
+    })
+}
+
+/// Decode a response carrying an error code followed by `bt_le_oob`
+///
+/// Fields follow Zephyr's struct order: address, then the random and confirm
+/// values of the LE Secure Connections OOB data.
+fn decode_oob_get_local_response(payload: &[u8]) -> Result<BtLeOob, RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let addr = decode_bt_addr_le_field(&mut decoder)?;
+    let rand = decoder.decode_byte_array()?;
+    let confirm = decoder.decode_byte_array()?;
+    decoder.expect_null()?;
+
+    Ok(BtLeOob {
+        addr,
+        rand,
+        confirm,
+    })
+}
+
+/// Decode a bt_le_ext_adv_create response: error code, then the set handle
+fn decode_ext_adv_create_response(payload: &[u8]) -> Result<AdvSetHandle, RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let handle = decoder.decode_u32()?;
+    decoder.expect_null()?;
+
+    Ok(AdvSetHandle(handle))
+}
+
+/// Decode a response carrying an error code followed by the attribute value
+fn decode_gatt_read_response<'b>(payload: &[u8], out: &'b mut [u8]) -> Result<&'b [u8], RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let value = decoder.decode_bytes(out)?;
+    decoder.expect_null()?;
+    Ok(value)
+}
+
+/// Decode a response carrying an error code followed by the random bytes
+///
+/// The remote must return exactly `out.len()` bytes.
+fn decode_rand_response(payload: &[u8], out: &mut [u8]) -> Result<(), RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    let err = decoder.decode_i32()?;
+    if err != 0 {
+        return Err(RpcError::Remote(err));
+    }
+
+    let len = out.len();
+    if decoder.decode_bytes(out)?.len() != len {
+        return Err(RpcError::InvalidResponse);
+    }
+    decoder.expect_null()
+}
+
+/// Decode an event payload based on its event ID
+///
+/// Events without a decoder are returned as `Event::Unknown` rather than
+/// treated as an error. This is exposed for testing purposes.
+#[doc(hidden)]
+pub fn decode_event(evt_id: u8, payload: &[u8]) -> Result<Event<'_>, RpcError> {
+    let mut decoder = PacketDecoder::new(payload);
+
+    match evt_id {
+        BT_LE_SCAN_CB_RPC_EVT => {
+            let addr = decode_bt_addr_le_field(&mut decoder)?;
+            let rssi = decoder.decode_i8()?;
+            let adv_type = decoder.decode_u8()?;
+            let data = decoder.decode_bytes_borrowed()?;
+            Ok(Event::ScanRecv {
+                addr,
+                rssi,
+                adv_type,
+                data,
+            })
+        }
+        BT_CONN_CB_CONNECTED_RPC_EVT => {
+            let conn = decode_conn_handle(&mut decoder)?;
+            let err = decoder.decode_u8()?;
+            Ok(Event::Connected { conn, err })
+        }
+        BT_CONN_CB_DISCONNECTED_RPC_EVT => {
+            let conn = decode_conn_handle(&mut decoder)?;
+            let reason = decoder.decode_u8()?;
+            Ok(Event::Disconnected { conn, reason })
+        }
+        BT_READY_CB_RPC_EVT => {
+            let err = decoder.decode_i32()?;
+            Ok(Event::Ready { err })
+        }
+        cmd => Ok(Event::Unknown { cmd, payload }),
+    }
+}
+
+/// Decode a connection handle serialized as a uint
+fn decode_conn_handle(decoder: &mut PacketDecoder) -> Result<ConnHandle, RpcError> {
+    decoder.decode_u32().map(ConnHandle)
+}
+
+/// Encode a bt_addr_le_t as a 7-byte string (type + address)
+fn encode_bt_addr_le<const N: usize>(
+    builder: PacketBuilder<N>,
+    addr: &BtAddrLe,
+) -> Result<PacketBuilder<N>, CborError> {
+    let mut bytes = [0u8; BT_ADDR_LE_SIZE];
+    bytes[0] = addr.addr_type;
+    bytes[1..].copy_from_slice(&addr.addr);
+    builder.cbor_bytes(&bytes)
+}
+
+/// Decode a bt_addr_le_t serialized as a byte string of exactly 7 bytes
+/// (type + address)
+fn decode_bt_addr_le_field(decoder: &mut PacketDecoder) -> Result<BtAddrLe, RpcError> {
+    let [addr_type, addr @ ..] = decoder.decode_byte_array::<BT_ADDR_LE_SIZE>()?;
+    Ok(BtAddrLe { addr_type, addr })
+}
+
+/// Encode a bt_uuid as a byte string of its type followed by its value
+fn encode_bt_uuid<const N: usize>(
+    builder: PacketBuilder<N>,
+    uuid: &BtUuid,
+) -> Result<PacketBuilder<N>, CborError> {
+    match uuid {
+        BtUuid::U16(value) => {
+            let [lo, hi] = value.to_le_bytes();
+            builder.cbor_bytes(&[BT_UUID_TYPE_16, lo, hi])
+        }
+        BtUuid::U32(value) => {
+            let [b0, b1, b2, b3] = value.to_le_bytes();
+            builder.cbor_bytes(&[BT_UUID_TYPE_32, b0, b1, b2, b3])
+        }
+        BtUuid::U128(value) => {
+            let mut bytes = [0u8; 17];
+            bytes[0] = BT_UUID_TYPE_128;
+            bytes[1..].copy_from_slice(value);
+            builder.cbor_bytes(&bytes)
+        }
+    }
+}
+
 /// Encode a single bt_data structure
 fn encode_bt_data<const N: usize>(
     mut builder: PacketBuilder<N>,
@@ -259,37 +2620,104 @@ fn encode_bt_data<const N: usize>(
     Ok(builder)
 }
 
+/// Exact number of bytes `data` takes when encoded in a command
+///
+/// Matches what the advertising commands emit per element: the type and the
+/// length as uints, then the data as a byte string. Summing this over the AD
+/// and SD elements gives the variable part of a packet, for sizing `N`.
+pub fn bt_data_encoded_len(data: &BtData) -> usize {
+    let len = data.data.len();
+    cbor_uint_len(data.data_type as u64)
+        + cbor_uint_len(len as u64)
+        + cbor_uint_len(len as u64)
+        + len
+}
+
+/// Length of a CBOR uint, which is also the header length of a byte string
+fn cbor_uint_len(value: u64) -> usize {
+    match value {
+        0..=23 => 1,
+        24..=0xFF => 2,
+        0x100..=0xFFFF => 3,
+        0x1_0000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
 /// Calculate scratchpad size for bt_le_adv_start
 ///
 /// Based on C implementation in bt_rpc_gap_client.c:
 /// - For each bt_data: NRF_RPC_SCRATCHPAD_ALIGN(sizeof(struct bt_data)) + NRF_RPC_SCRATCHPAD_ALIGN(data_len)
 /// - bt_le_adv_param_sp_size(param) which is 0 if peer is None
 fn calculate_scratchpad_size(param: &BtLeAdvParam, ad: &[BtData], sd: &[BtData]) -> usize {
-    const BT_DATA_SIZE: usize = 8; // sizeof(struct bt_data) in C
-    let mut size = 0;
+    let mut size = scratchpad_for_bt_data(ad) + scratchpad_for_bt_data(sd);
 
-    // bt_data structures for ad
-    for ad_item in ad {
-        size += align_to_4(BT_DATA_SIZE); // sizeof(struct bt_data)
-        size += align_to_4(ad_item.data.len()); // actual data
+    // peer address if present
+    if param.peer.is_some() {
+        size += align_to_4(BT_ADDR_LE_SIZE);
     }
 
-    // bt_data structures for sd
-    for sd_item in sd {
-        size += align_to_4(BT_DATA_SIZE); // sizeof(struct bt_data)
-        size += align_to_4(sd_item.data.len()); // actual data
-    }
+    size
+}
 
-    // peer address if present
-    if param.peer.is_some() {
-        size += align_to_4(7); // sizeof(bt_addr_le_t)
+/// Calculate scratchpad size needed to decode an array of bt_data
+///
+/// Each element takes an aligned `struct bt_data` plus its aligned data.
+pub(crate) fn scratchpad_for_bt_data(items: &[BtData]) -> usize {
+    const BT_DATA_SIZE: usize = 8; // sizeof(struct bt_data) in C
+
+    items
+        .iter()
+        .map(|item| align_to_4(BT_DATA_SIZE) + align_to_4(item.data.len()))
+        .sum()
+}
+
+/// Calculate scratchpad size for a GATT service's attribute table
+///
+/// Every attribute takes a `struct bt_gatt_attr` plus its UUID; service and
+/// characteristic declarations also carry their user data.
+pub(crate) fn scratchpad_for_gatt_service(svc: &GattService) -> usize {
+    const BT_GATT_ATTR_SIZE: usize = 20; // sizeof(struct bt_gatt_attr) in C
+    const BT_GATT_CHRC_SIZE: usize = 8; // sizeof(struct bt_gatt_chrc) in C
+    const UUID16_SIZE: usize = 4; // sizeof(struct bt_uuid_16) in C
+
+    let uuid_size = |uuid: &BtUuid| match uuid {
+        BtUuid::U16(_) => align_to_4(UUID16_SIZE),
+        BtUuid::U32(_) => align_to_4(8), // sizeof(struct bt_uuid_32)
+        BtUuid::U128(_) => align_to_4(17), // sizeof(struct bt_uuid_128)
+    };
+
+    // Primary service declaration, with the service UUID as user data
+    let mut size = align_to_4(BT_GATT_ATTR_SIZE) + align_to_4(UUID16_SIZE) + uuid_size(&svc.uuid);
+
+    for chrc in svc.characteristics {
+        // Declaration with its bt_gatt_chrc user data
+        size += align_to_4(BT_GATT_ATTR_SIZE) + align_to_4(UUID16_SIZE);
+        size += align_to_4(BT_GATT_CHRC_SIZE) + uuid_size(&chrc.uuid);
+        // Value
+        size += align_to_4(BT_GATT_ATTR_SIZE) + uuid_size(&chrc.uuid);
     }
 
     size
 }
 
+/// sizeof(bt_addr_le_t) in C
+const BT_ADDR_LE_SIZE: usize = 7;
+
+/// `bt_uuid` type tags
+const BT_UUID_TYPE_16: u8 = 0;
+const BT_UUID_TYPE_32: u8 = 1;
+const BT_UUID_TYPE_128: u8 = 2;
+
+/// GATT declaration UUIDs
+const BT_UUID_GATT_PRIMARY: u16 = 0x2800;
+const BT_UUID_GATT_CHRC: u16 = 0x2803;
+
 /// Align size to 4-byte boundary (required by NRF RPC scratchpad)
-fn align_to_4(size: usize) -> usize {
+///
+/// Every buffer the remote decoder carves out of the scratchpad is rounded up
+/// with this, so command encoders should sum `align_to_4` of each part.
+pub(crate) fn align_to_4(size: usize) -> usize {
     (size + 3) & !3
 }
 
@@ -308,7 +2736,7 @@ mod tests {
             id: 0,
             sid: 0,
             secondary_max_skip: 0,
-            options: 0x03,
+            options: BtLeAdvOpt::CONNECTABLE | BtLeAdvOpt::ONE_TIME,
             interval_min: 160,
             interval_max: 240,
             peer: None,
@@ -335,4 +2763,735 @@ mod tests {
 
         assert_eq!(packet.as_slice(), expected);
     }
+
+    #[test]
+    fn test_bt_le_adv_start_raw_encoding() {
+        let param = BtLeAdvParam {
+            id: 0,
+            sid: 0,
+            secondary_max_skip: 0,
+            options: BtLeAdvOpt::CONNECTABLE | BtLeAdvOpt::ONE_TIME,
+            interval_min: 160,
+            interval_max: 240,
+            peer: None,
+        };
+
+        let ad = [0x02, BT_DATA_FLAGS, BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR];
+        let packet = encode_bt_le_adv_start_raw::<64>(0x00, 0x00, 0x00, &param, &ad, &[]).unwrap();
+
+        let expected = &[
+            0x80, 0x12, 0xFF, 0x00, 0x00, // header
+            0x04, // scratchpad: align(3) + align(0)
+            0x00, 0x00, 0x00, 0x03, 0x18, 0xA0, 0x18, 0xF0, 0xF6, // bt_le_adv_param
+            0x43, 0x02, 0x01, 0x06, // ad as one byte string
+            0x40, // empty sd
+            0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_bt_le_adv_update_data_encoding() {
+        // Same data as the bt_le_adv_start trace, without the adv param block
+        let ad_data = [BtData {
+            data_type: BT_DATA_FLAGS,
+            data: &[BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR],
+        }];
+
+        let sd_data = [BtData {
+            data_type: BT_DATA_NAME_COMPLETE,
+            data: b"Nordic_PS",
+        }];
+
+        let packet =
+            encode_bt_le_adv_update_data::<256>(0x00, 0x00, 0x00, &ad_data, &sd_data).unwrap();
+
+        let expected = &[
+            0x80, 0x0B, 0xFF, 0x00, 0x00, 0x18, 0x20, 0x01, 0x01, 0x01, 0x41, 0x06, 0x01, 0x09,
+            0x09, 0x49, 0x4E, 0x6F, 0x72, 0x64, 0x69, 0x63, 0x5F, 0x50, 0x53, 0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_decode_scan_report_event() {
+        let payload = [
+            0x47, 0x01, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, // addr (random)
+            0x38, 0x3B, // rssi -60
+            0x00, // adv_type ADV_IND
+            0x43, 0x02, 0x01, 0x06, // flags AD element
+            0xF6,
+        ];
+
+        let event = decode_event(BT_LE_SCAN_CB_RPC_EVT, &payload).unwrap();
+        let Event::ScanRecv {
+            addr,
+            rssi,
+            adv_type,
+            data,
+        } = event
+        else {
+            panic!("expected ScanRecv, got {event:?}");
+        };
+        assert_eq!(addr.addr_type, 0x01);
+        assert_eq!(addr.addr, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!(rssi, -60);
+        assert_eq!(adv_type, 0x00);
+        assert_eq!(data, &[0x02, 0x01, 0x06]);
+    }
+
+    #[test]
+    fn test_decode_conn_events() {
+        assert!(matches!(
+            decode_event(BT_CONN_CB_CONNECTED_RPC_EVT, &[0x03, 0x00, 0xF6]),
+            Ok(Event::Connected {
+                conn: ConnHandle(3),
+                err: 0
+            })
+        ));
+        assert!(matches!(
+            decode_event(BT_CONN_CB_DISCONNECTED_RPC_EVT, &[0x03, 0x13, 0xF6]),
+            Ok(Event::Disconnected {
+                conn: ConnHandle(3),
+                reason: BT_HCI_ERR_REMOTE_USER_TERM_CONN
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_unknown_event() {
+        let event = decode_event(0x42, &[0x01, 0xF6]).unwrap();
+        assert!(matches!(
+            event,
+            Event::Unknown {
+                cmd: 0x42,
+                payload: &[0x01, 0xF6]
+            }
+        ));
+    }
+
+    #[test]
+    fn test_decode_event_rejects_short_address() {
+        let payload = [0x43, 0x01, 0x11, 0x22, 0x38, 0x3B, 0x40, 0xF6];
+        assert!(matches!(
+            decode_event(BT_LE_SCAN_CB_RPC_EVT, &payload),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_decode_event_rejects_long_address() {
+        // 8-byte address, one past bt_addr_le_t
+        let payload = [
+            0x48, 0x01, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x38, 0x3B, 0x00, 0x40, 0xF6,
+        ];
+        assert!(matches!(
+            decode_event(BT_LE_SCAN_CB_RPC_EVT, &payload),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_manufacturer_data_encoding() {
+        // Nordic company ID 0x0059 followed by one byte of payload
+        let ad = BtData::manufacturer_data(&[0x59, 0x00, 0x01]);
+        let packet = encode_bt_data(PacketBuilder::<32>::new(), &ad).unwrap();
+
+        assert_eq!(
+            packet.as_slice(),
+            &[0x18, 0xFF, 0x03, 0x43, 0x59, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_appearance_and_tx_power_encoding() {
+        let appearance = 0x0340u16.to_le_bytes();
+        let ad = BtData::appearance(&appearance);
+        let packet = encode_bt_data(PacketBuilder::<32>::new(), &ad).unwrap();
+        assert_eq!(packet.as_slice(), &[0x18, 0x19, 0x02, 0x42, 0x40, 0x03]);
+
+        // -4 dBm
+        let ad = BtData::tx_power(&[-4i8 as u8]);
+        let packet = encode_bt_data(PacketBuilder::<32>::new(), &ad).unwrap();
+        assert_eq!(packet.as_slice(), &[0x0A, 0x01, 0x41, 0xFC]);
+    }
+
+    #[test]
+    fn test_bt_le_adv_start_min_len() {
+        let param = BtLeAdvParam::builder()
+            .interval_min(0x10)
+            .interval_max(0x10)
+            .options(BtLeAdvOpt::NONE)
+            .build();
+        let packet =
+            encode_bt_le_adv_start::<BT_LE_ADV_START_MIN_LEN>(0, 0, 0, &param, &[], &[]).unwrap();
+        assert_eq!(packet.len(), BT_LE_ADV_START_MIN_LEN);
+    }
+
+    #[test]
+    fn test_bt_data_encoded_len() {
+        // Byte string and length headers grow from 1 to 2 bytes at 24, and to
+        // 3 bytes at 256
+        for len in [0, 1, 23, 24, 255, 256] {
+            let bytes = std::vec![0xAA; len];
+            let data = BtData {
+                data_type: BT_DATA_MANUFACTURER_DATA,
+                data: &bytes,
+            };
+            let packet = encode_bt_data(PacketBuilder::<512>::new(), &data).unwrap();
+            assert_eq!(bt_data_encoded_len(&data), packet.len(), "len {len}");
+        }
+
+        // Types up to 23 fit in the initial byte
+        let data = BtData::flags(&[0x06]);
+        assert_eq!(bt_data_encoded_len(&data), 1 + 1 + 1 + 1);
+    }
+
+    #[test]
+    fn test_ad_struct_iter() {
+        // Complete local name "Nordic_PS"
+        let name = [
+            0x0A, 0x09, 0x4E, 0x6F, 0x72, 0x64, 0x69, 0x63, 0x5F, 0x50, 0x53,
+        ];
+        let mut iter = AdStructIter::new(&name);
+        let ad = iter.next().unwrap();
+        assert_eq!(ad.data_type, BT_DATA_NAME_COMPLETE);
+        assert_eq!(ad.data, b"Nordic_PS");
+        assert!(iter.next().is_none());
+
+        // Flags, 16-bit UUIDs, then zero padding after the significant part
+        let multi = [
+            0x02, 0x01, 0x06, // flags
+            0x05, 0x03, 0x0F, 0x18, 0x0A, 0x18, // UUIDs 0x180F, 0x180A
+            0x00, 0x00,
+        ];
+        let ads: std::vec::Vec<_> = AdStructIter::new(&multi)
+            .map(|ad| (ad.data_type, ad.data))
+            .collect();
+        assert_eq!(
+            ads,
+            [
+                (BT_DATA_FLAGS, &[0x06][..]),
+                (BT_DATA_UUID16_ALL, &[0x0F, 0x18, 0x0A, 0x18][..]),
+            ]
+        );
+
+        // A length running past the end stops iteration
+        let truncated = [0x02, 0x01, 0x06, 0x05, 0x09, 0x41];
+        assert_eq!(AdStructIter::new(&truncated).count(), 1);
+    }
+
+    #[test]
+    fn test_bt_data_buf_little_endian() {
+        let appearance = BtDataBuf::appearance_u16(0x0340);
+        let ad = appearance.as_bt_data();
+        assert_eq!(ad.data_type, BT_DATA_GAP_APPEARANCE);
+        assert_eq!(ad.data, &[0x40, 0x03]);
+
+        let uuids = BtDataBuf::<4>::service_uuid16_list(&[0x180F, 0x180A]).unwrap();
+        let ad = uuids.as_bt_data();
+        assert_eq!(ad.data_type, BT_DATA_UUID16_ALL);
+        assert_eq!(ad.data, &[0x0F, 0x18, 0x0A, 0x18]);
+
+        assert!(matches!(
+            BtDataBuf::<2>::service_uuid16_list(&[0x180F, 0x180A]),
+            Err(CborError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_bt_data_buf_service_uuid_list() {
+        let battery: BtUuid = "180f".parse().unwrap();
+        let uuids = BtDataBuf::<4>::service_uuid_list([battery, BtUuid::U16(0x180A)]).unwrap();
+        let ad = uuids.as_bt_data();
+        assert_eq!(ad.data_type, BT_DATA_UUID16_ALL);
+        assert_eq!(ad.data, &[0x0F, 0x18, 0x0A, 0x18]);
+
+        // Wider UUIDs are rejected rather than truncated
+        assert!(matches!(
+            BtDataBuf::<8>::service_uuid_list([battery, BtUuid::U32(0x0000_180A)]),
+            Err(CborError::EncodingError)
+        ));
+        assert!(matches!(
+            BtDataBuf::<32>::service_uuid_list([BtUuid::U128([0; 16])]),
+            Err(CborError::EncodingError)
+        ));
+    }
+
+    #[test]
+    fn test_service_uuid16_encoding() {
+        // Battery service 0x180F
+        let uuid = BtData::service_uuid16(BtUuid::U16(0x180F)).unwrap();
+        let packet = encode_bt_data(PacketBuilder::<32>::new(), &uuid.as_bt_data()).unwrap();
+
+        assert_eq!(packet.as_slice(), &[0x03, 0x02, 0x42, 0x0F, 0x18]);
+        assert!(matches!(
+            BtData::service_uuid16(BtUuid::U32(0x0000_180F)),
+            Err(CborError::EncodingError)
+        ));
+    }
+
+    #[test]
+    fn test_adv_param_builder() {
+        let default = BtLeAdvParam::builder().build();
+        let connectable = BtLeAdvParam::connectable();
+        assert_eq!(default.options, connectable.options);
+        assert_eq!(default.interval_min, connectable.interval_min);
+        assert_eq!(default.interval_max, connectable.interval_max);
+
+        let peer = BtAddrLe {
+            addr_type: 0x01,
+            addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+        };
+        let param = BtLeAdvParam::builder()
+            .id(1)
+            .interval_min(320)
+            .interval_max(400)
+            .options(BT_LE_ADV_OPT_CONNECTABLE | BT_LE_ADV_OPT_ONE_TIME)
+            .peer(peer)
+            .build();
+        assert_eq!(param.id, 1);
+        assert_eq!(param.interval_min, 320);
+        assert_eq!(param.interval_max, 400);
+        assert_eq!(param.options.bits(), 0x03);
+        assert_eq!(param.peer.unwrap().addr, peer.addr);
+    }
+
+    #[test]
+    fn test_adv_opt_bit_ops() {
+        let opt = BtLeAdvOpt::CONNECTABLE | BtLeAdvOpt::USE_IDENTITY;
+        assert_eq!(opt.bits(), 0x05);
+        assert!(opt.contains(BtLeAdvOpt::CONNECTABLE));
+        assert!(!opt.contains(BtLeAdvOpt::ONE_TIME));
+        assert_eq!(opt & BtLeAdvOpt::USE_IDENTITY, BtLeAdvOpt::USE_IDENTITY);
+        assert_eq!(BtLeAdvOpt::from(0x0400), BtLeAdvOpt::EXT_ADV);
+        assert_eq!(u32::from(BtLeAdvOpt::SCANNABLE), 0x0200);
+    }
+
+    #[test]
+    fn test_bt_conn_le_create_encoding() {
+        let peer = BtAddrLe {
+            addr_type: 0x01,
+            addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+        };
+        let conn_param = BtLeConnParam {
+            interval_min: 24,
+            interval_max: 40,
+            latency: 0,
+            timeout: 400,
+        };
+
+        let packet = encode_bt_conn_le_create::<64>(
+            0x00,
+            0x00,
+            0x00,
+            &peer,
+            &BtConnLeCreateParam::create_conn(),
+            &conn_param,
+        )
+        .unwrap();
+
+        let expected = &[
+            0x80, 0x08, 0xFF, 0x00, 0x00, 0x08, // scratchpad: align(7)
+            0x47, 0x01, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, // peer
+            0x00, 0x18, 0x60, 0x18, 0x60, 0x00, 0x00, 0x00, // create param
+            0x18, 0x18, 0x18, 0x28, 0x00, 0x19, 0x01, 0x90, // conn param
+            0xF6,
+        ];
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_decode_conn_handle_response() {
+        assert_eq!(
+            decode_conn_handle_response(&[0x00, 0x03, 0xF6]).unwrap(),
+            ConnHandle(3)
+        );
+        assert!(matches!(
+            decode_conn_handle_response(&[0x2B, 0xF6]),
+            Err(RpcError::Remote(-12))
+        ));
+
+        // Error code outside the i32 range, not truncated to 0
+        assert!(matches!(
+            decode_conn_handle_response(&[
+                0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xF6
+            ]),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        // Missing terminator
+        assert!(matches!(
+            decode_conn_handle_response(&[0x00, 0x03]),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_bt_le_adv_start_directed_encoding() {
+        let param = BtLeAdvParam::builder()
+            .peer(BtAddrLe {
+                addr_type: 0x00,
+                addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+            })
+            .build();
+
+        let packet = encode_bt_le_adv_start::<64>(0x00, 0x00, 0x00, &param, &[], &[]).unwrap();
+
+        let expected = &[
+            0x80, 0x04, 0xFF, 0x00, 0x00, 0x08, // scratchpad: align(7) for the peer
+            0x00, 0x00, 0x00, 0x01, 0x18, 0xA0, 0x18, 0xF0, // param
+            0x47, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, // peer
+            0x00, 0x00, // empty ad, sd
+            0xF6,
+        ];
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_bt_addr_le_parse() {
+        let addr: BtAddrLe = "C0:11:22:33:44:55 (random)".parse().unwrap();
+        assert_eq!(addr.addr_type, BT_ADDR_LE_RANDOM);
+        assert_eq!(addr.addr, [0x55, 0x44, 0x33, 0x22, 0x11, 0xC0]);
+
+        let addr: BtAddrLe = "aa:bb:cc:dd:ee:ff (public)".parse().unwrap();
+        assert_eq!(addr.addr_type, BT_ADDR_LE_PUBLIC);
+        assert_eq!(addr.addr, [0xFF, 0xEE, 0xDD, 0xCC, 0xBB, 0xAA]);
+
+        let addr: BtAddrLe = "AA:BB:CC:DD:EE:FF".parse().unwrap();
+        assert_eq!(addr.addr_type, BT_ADDR_LE_PUBLIC);
+    }
+
+    #[test]
+    fn test_bt_addr_le_parse_malformed() {
+        for input in [
+            "",
+            "AA:BB:CC:DD:EE",
+            "AA:BB:CC:DD:EE:FF:00",
+            "AA:BB:CC:DD:EE:GG",
+            "AAA:BB:CC:DD:EE:F",
+            "AA:BB:CC:DD:EE:FF (static)",
+        ] {
+            assert_eq!(input.parse::<BtAddrLe>(), Err(AddrParseError), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_bt_uuid_parse() {
+        assert_eq!("180f".parse(), Ok(BtUuid::U16(0x180F)));
+        assert_eq!("0x2A19".parse(), Ok(BtUuid::U16(0x2A19)));
+        assert_eq!("0000180F".parse(), Ok(BtUuid::U32(0x180F)));
+
+        let uuid: BtUuid = "6e400001-b5a3-f393-e0a9-e50e24dcca9e".parse().unwrap();
+        assert_eq!(
+            uuid,
+            BtUuid::U128([
+                0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x01, 0x00,
+                0x40, 0x6E,
+            ])
+        );
+
+        for input in [
+            "",
+            "18f",
+            "+18f",
+            "180g",
+            "6e400001-b5a3-f393-e0a9",
+            "6e400001-b5a3-f393-e0a9-e50e24dcca9e-00",
+            "6e40001-b5a3-f393-e0a9-e50e24dcca9e0",
+        ] {
+            assert_eq!(input.parse::<BtUuid>(), Err(UuidParseError), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_bt_uuid32_encoding() {
+        let packet = encode_bt_uuid(PacketBuilder::<8>::new(), &BtUuid::U32(0x0000180F)).unwrap();
+        assert_eq!(packet.as_slice(), &[0x45, 0x01, 0x0F, 0x18, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_bt_addr_le_display_round_trip() {
+        let addr = BtAddrLe {
+            addr_type: BT_ADDR_LE_RANDOM,
+            addr: [0x55, 0x44, 0x33, 0x22, 0x11, 0xC0],
+        };
+        let s = format!("{}", addr);
+        assert_eq!(s, "C0:11:22:33:44:55 (random)");
+        assert_eq!(s.parse::<BtAddrLe>().unwrap(), addr);
+    }
+
+    #[test]
+    fn test_align_to_4() {
+        assert_eq!(align_to_4(0), 0);
+        assert_eq!(align_to_4(1), 4);
+        assert_eq!(align_to_4(2), 4);
+        assert_eq!(align_to_4(3), 4);
+        assert_eq!(align_to_4(4), 4);
+        assert_eq!(align_to_4(5), 8);
+    }
+
+    #[test]
+    fn test_scratchpad_for_bt_data_odd_lengths() {
+        let data = [0u8; 5];
+        // Each element costs 8 bytes for the struct plus its data rounded up to 4
+        for (len, expected) in [(1, 12), (2, 12), (3, 12), (5, 16)] {
+            let items = [BtData {
+                data_type: BT_DATA_MANUFACTURER_DATA,
+                data: &data[..len],
+            }];
+            assert_eq!(scratchpad_for_bt_data(&items), expected, "data len {len}");
+        }
+
+        assert_eq!(scratchpad_for_bt_data(&[]), 0);
+    }
+
+    #[test]
+    fn test_decode_rand_response() {
+        let mut out = [0u8; 4];
+        decode_rand_response(&[0x00, 0x44, 0xDE, 0xAD, 0xBE, 0xEF, 0xF6], &mut out).unwrap();
+        assert_eq!(out, [0xDE, 0xAD, 0xBE, 0xEF]);
+
+        // Fewer bytes than requested
+        assert!(matches!(
+            decode_rand_response(&[0x00, 0x42, 0xDE, 0xAD, 0xF6], &mut out),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        // Non-zero error code (-5)
+        assert!(matches!(
+            decode_rand_response(&[0x24, 0x40, 0xF6], &mut out),
+            Err(RpcError::Remote(-5))
+        ));
+
+        // Extra field before the terminator
+        assert!(matches!(
+            decode_rand_response(&[0x00, 0x44, 0xDE, 0xAD, 0xBE, 0xEF, 0x00, 0xF6], &mut out),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_bt_gatt_notify_encoding() {
+        let packet = encode_bt_gatt_notify::<64>(
+            0x00,
+            0x00,
+            0x00,
+            ConnHandle(1),
+            0x0012,
+            &[0x01, 0x02, 0x03, 0x04],
+        )
+        .unwrap();
+
+        let expected = &[
+            0x80, 0x0D, 0xFF, 0x00, 0x00, // header
+            0x04, // scratchpad: align(4)
+            0x01, // conn
+            0x12, // attr handle
+            0x44, 0x01, 0x02, 0x03, 0x04, // 4-byte value
+            0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_bt_le_conn_param_update_encoding() {
+        // Distinct values so a swapped field shows up in the bytes
+        let param = BtLeConnParam {
+            interval_min: 6,
+            interval_max: 7,
+            latency: 8,
+            timeout: 9,
+        };
+        let packet =
+            encode_bt_le_conn_param_update::<64>(0x00, 0x00, 0x00, ConnHandle(2), &param).unwrap();
+
+        let expected = &[
+            0x80, 0x10, 0xFF, 0x00, 0x00, // header
+            0x02, // conn
+            0x06, 0x07, 0x08, 0x09, // interval_min, interval_max, latency, timeout
+            0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_decode_conn_info_response() {
+        let payload = [
+            0x00, // err
+            0x01, // role: peripheral
+            0x47, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, // peer addr (public)
+            0x18, 0x28, // interval 40
+            0x02, // latency 2
+            0x19, 0x01, 0x90, // timeout 400
+            0xF6,
+        ];
+
+        let info = decode_conn_info_response(&payload).unwrap();
+        assert_eq!(
+            info,
+            BtConnInfo {
+                role: 1,
+                interval: 40,
+                latency: 2,
+                timeout: 400,
+                addr: BtAddrLe {
+                    addr_type: BT_ADDR_LE_PUBLIC,
+                    addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+                },
+            }
+        );
+
+        // -ENOTCONN
+        assert!(matches!(
+            decode_conn_info_response(&[0x38, 0x7F, 0xF6]),
+            Err(RpcError::Remote(-128))
+        ));
+    }
+
+    #[test]
+    fn test_bt_gatt_service_register_encoding() {
+        let chrcs = [GattCharacteristic {
+            uuid: BtUuid::U16(0x2A19),
+            properties: BT_GATT_CHRC_READ | BT_GATT_CHRC_NOTIFY,
+            permissions: BT_GATT_PERM_READ,
+        }];
+        let svc = GattService {
+            uuid: BtUuid::U16(0x180F),
+            characteristics: &chrcs,
+        };
+
+        let packet = encode_bt_gatt_service_register::<64>(0x00, 0x00, 0x00, &svc).unwrap();
+
+        let expected = &[
+            0x80, 0x13, 0xFF, 0x00, 0x00, // header
+            0x18, 0x58, // scratchpad: 3 attrs (60) + 3 uuids (12) + svc uuid (4) + chrc (12)
+            0x03, // attribute count
+            0x43, 0x00, 0x00, 0x28, 0x01, 0x43, 0x00, 0x0F, 0x18, // primary service
+            0x43, 0x00, 0x03, 0x28, 0x01, 0x12, 0x43, 0x00, 0x19, 0x2A, // declaration
+            0x43, 0x00, 0x19, 0x2A, 0x01, 0xF6, // value
+            0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_scratchpad_for_gatt_service_uuid128() {
+        let chrcs = [GattCharacteristic {
+            uuid: BtUuid::U128([0; 16]),
+            properties: BT_GATT_CHRC_WRITE,
+            permissions: BT_GATT_PERM_WRITE,
+        }];
+        let svc = GattService {
+            uuid: BtUuid::U128([0; 16]),
+            characteristics: &chrcs,
+        };
+
+        // 128-bit UUIDs take 20 bytes each instead of 4
+        assert_eq!(scratchpad_for_gatt_service(&svc), 88 + 3 * 16);
+    }
+
+    #[test]
+    fn test_bt_gatt_write_encoding() {
+        let packet =
+            encode_bt_gatt_write::<64>(0x00, 0x00, 0x00, ConnHandle(1), 0x0012, 0, &[0x01, 0x00])
+                .unwrap();
+
+        let expected = &[
+            0x80, 0x14, 0xFF, 0x00, 0x00, // header
+            0x04, // scratchpad: align(2)
+            0x01, // conn
+            0x12, // handle
+            0x00, // offset
+            0x42, 0x01, 0x00, // value
+            0xF6,
+        ];
+
+        assert_eq!(packet.as_slice(), expected);
+    }
+
+    #[test]
+    fn test_decode_gatt_read_response() {
+        let mut out = [0u8; 8];
+        let value = decode_gatt_read_response(&[0x00, 0x42, 0x64, 0x00, 0xF6], &mut out).unwrap();
+        assert_eq!(value, &[0x64, 0x00]);
+
+        // Longer than the caller's buffer
+        let mut small = [0u8; 1];
+        assert!(matches!(
+            decode_gatt_read_response(&[0x00, 0x42, 0x64, 0x00, 0xF6], &mut small),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        // Missing terminator
+        assert!(matches!(
+            decode_gatt_read_response(&[0x00, 0x42, 0x64, 0x00], &mut out),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_decode_oob_get_local_response() {
+        let mut payload = std::vec![
+            0x00, // err
+            0x47, 0x01, 0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, // addr (random)
+            0x50, // rand: 16-byte string
+        ];
+        payload.extend(0x10..0x20u8);
+        payload.push(0x50); // confirm: 16-byte string
+        payload.extend(0x20..0x30u8);
+        payload.push(0xF6);
+
+        let oob = decode_oob_get_local_response(&payload).unwrap();
+        assert_eq!(
+            oob.addr,
+            BtAddrLe {
+                addr_type: BT_ADDR_LE_RANDOM,
+                addr: [0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6],
+            }
+        );
+        assert_eq!(oob.rand, core::array::from_fn(|i| 0x10 + i as u8));
+        assert_eq!(oob.confirm, core::array::from_fn(|i| 0x20 + i as u8));
+
+        // A short confirm value is rejected rather than zero-padded
+        let short = [&payload[..26], &[0x4F], &payload[27..42], &[0xF6]].concat();
+        assert!(matches!(
+            decode_oob_get_local_response(&short),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_decode_ext_adv_create_response() {
+        assert_eq!(
+            decode_ext_adv_create_response(&[0x00, 0x02, 0xF6]).unwrap(),
+            AdvSetHandle(2)
+        );
+        // -12 (ENOMEM): no free advertising set
+        assert!(matches!(
+            decode_ext_adv_create_response(&[0x2B, 0xF6]),
+            Err(RpcError::Remote(-12))
+        ));
+        assert!(matches!(
+            decode_ext_adv_create_response(&[0x00, 0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_interval_conversions() {
+        assert_eq!(adv_interval_ms(100), 160);
+        assert_eq!(adv_interval_ms(150), 240);
+        assert_eq!(scan_interval_ms(60), 96);
+        assert_eq!(scan_window_ms(30), 48);
+        assert_eq!(scan_interval_ms(u16::MAX), u16::MAX);
+        assert_eq!(conn_interval_ms(7.5), 6);
+        assert_eq!(conn_interval_ms(30.0), 24);
+        assert_eq!(conn_interval_ms(4000.0), 3200);
+    }
 }
@@ -0,0 +1,141 @@
+//! Read buffering for transports that deliver a few bytes at a time
+//!
+//! Framing reads a packet in small pieces (length prefix, then body), and a
+//! UART driver may hand out only what arrived since the last call. Both mean
+//! many round-trips into the driver per packet. `BufferedTransport` reads as
+//! much as the driver has into an internal buffer and serves later reads from
+//! it.
+
+use crate::transport::AsyncTransport;
+
+/// Transport wrapper that reads in chunks of up to `CAP` bytes
+///
+/// Writes and flushes pass straight through. Place it below a framing
+/// wrapper, which then reads from the buffer instead of the driver.
+///
+/// This is a linear buffer, not a ring: it is refilled from the start, and
+/// only once every buffered byte has been read. A read therefore never waits
+/// on the driver while buffered bytes remain.
+///
+/// # Example
+///
+/// ```ignore
+/// let transport = FramedTransport::new(BufferedTransport::<_, 64>::new(uart));
+/// let mut ble = Ble::new(transport).await?;
+/// ```
+pub struct BufferedTransport<T: AsyncTransport, const CAP: usize> {
+    inner: T,
+    buf: [u8; CAP],
+    /// Unread bytes are `buf[head..tail]`
+    head: usize,
+    tail: usize,
+}
+
+impl<T: AsyncTransport, const CAP: usize> BufferedTransport<T, CAP> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            buf: [0u8; CAP],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Recover the wrapped transport, dropping any unread bytes
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Number of bytes read from the transport but not yet returned
+    pub fn buffered(&self) -> usize {
+        self.tail - self.head
+    }
+}
+
+impl<T: AsyncTransport, const CAP: usize> AsyncTransport for BufferedTransport<T, CAP> {
+    type Error = T::Error;
+
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        self.inner.write(data).await
+    }
+
+    /// Read buffered bytes into `buffer`, refilling the buffer once if empty
+    ///
+    /// Returns at most the bytes already buffered, so a read never waits for
+    /// more data than the transport delivered in one call. Reads of at least
+    /// `CAP` bytes into an empty buffer go straight to the transport.
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.head == self.tail {
+            if buffer.len() >= CAP {
+                return self.inner.read(buffer).await;
+            }
+            self.head = 0;
+            self.tail = self.inner.read(&mut self.buf).await?;
+        }
+
+        let n = buffer.len().min(self.buffered());
+        buffer[..n].copy_from_slice(&self.buf[self.head..self.head + n]);
+        self.head += n;
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::FramedTransport;
+    use crate::test_stream::stream;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_small_reads_are_served_from_buffer() {
+        let mut buffered = BufferedTransport::<_, 8>::new(stream(&[1, 2, 3, 4, 5, 6], 8));
+        let mut buf = [0u8; 2];
+
+        for expected in [[1, 2], [3, 4], [5, 6]] {
+            assert_eq!(block_on(buffered.read(&mut buf)).unwrap(), 2);
+            assert_eq!(buf, expected);
+        }
+        assert_eq!(buffered.buffered(), 0);
+        assert_eq!(block_on(buffered.read(&mut buf)).unwrap(), 0);
+        assert_eq!(buffered.into_inner().reads, 2);
+    }
+
+    #[test]
+    fn test_read_returns_only_buffered_bytes() {
+        let mut buffered = BufferedTransport::<_, 8>::new(stream(&[1, 2, 3, 4, 5], 3));
+        let mut buf = [0u8; 4];
+
+        assert_eq!(block_on(buffered.read(&mut buf)).unwrap(), 3);
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+        assert_eq!(block_on(buffered.read(&mut buf)).unwrap(), 2);
+        assert_eq!(&buf[..2], &[4, 5]);
+    }
+
+    #[test]
+    fn test_large_read_bypasses_buffer() {
+        let mut buffered = BufferedTransport::<_, 4>::new(stream(&[1, 2, 3, 4, 5, 6], 8));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(block_on(buffered.read(&mut buf)).unwrap(), 6);
+        assert_eq!(&buf[..6], &[1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_framing_over_buffer() {
+        // Two frames arriving in one burst need a single driver read
+        let rx = [0x02, 0x00, 0xAA, 0xBB, 0x01, 0x00, 0xCC];
+        let mut framed = FramedTransport::new(BufferedTransport::<_, 16>::new(stream(&rx, 16)));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(block_on(framed.read(&mut buf)).unwrap(), 2);
+        assert_eq!(&buf[..2], &[0xAA, 0xBB]);
+        assert_eq!(block_on(framed.read(&mut buf)).unwrap(), 1);
+        assert_eq!(buf[0], 0xCC);
+        assert_eq!(framed.into_inner().into_inner().reads, 1);
+    }
+}
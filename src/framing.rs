@@ -0,0 +1,445 @@
+//! Packet framing for byte-stream transports
+//!
+//! nRF RPC expects every `read` to yield exactly one packet, which a raw UART
+//! cannot guarantee. The wrappers in this module add packet boundaries on top
+//! of any `AsyncTransport` and implement `AsyncTransport` themselves, so they
+//! can be dropped in wherever a transport is expected.
+
+use core::fmt;
+
+use crate::transport::{AsyncTransport, TransportError};
+
+/// Error returned by the framing wrappers
+#[derive(Debug)]
+pub enum FramingError<E> {
+    /// The underlying transport failed
+    Transport(E),
+    /// The frame does not fit in the caller's buffer or exceeds the maximum frame size
+    FrameTooLarge,
+    /// The stream ended in the middle of a frame
+    UnexpectedEof,
+    /// A frame could not be decoded and was discarded
+    InvalidFrame,
+    /// The incoming frame is longer than the caller's buffer and was discarded
+    BufferTooSmall { needed: usize },
+}
+
+impl<E: fmt::Debug> fmt::Display for FramingError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::Transport(e) => write!(f, "Transport error: {:?}", e),
+            FramingError::FrameTooLarge => write!(f, "Frame too large"),
+            FramingError::UnexpectedEof => write!(f, "Unexpected end of stream"),
+            FramingError::InvalidFrame => write!(f, "Invalid frame"),
+            FramingError::BufferTooSmall { needed } => {
+                write!(f, "Buffer too small for {}-byte frame", needed)
+            }
+        }
+    }
+}
+
+impl<E: TransportError> TransportError for FramingError<E> {
+    fn needed_len(&self) -> Option<usize> {
+        match self {
+            FramingError::Transport(e) => e.needed_len(),
+            FramingError::BufferTooSmall { needed } => Some(*needed),
+            _ => None,
+        }
+    }
+}
+
+/// Length-prefixed framing
+///
+/// Each packet is preceded by its length as a 2-byte little-endian integer.
+///
+/// # Example
+///
+/// ```ignore
+/// let transport = FramedTransport::new(uart);
+/// let mut ble = Ble::new(transport).await?;
+/// ```
+pub struct FramedTransport<T: AsyncTransport> {
+    inner: T,
+}
+
+impl<T: AsyncTransport> FramedTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Recover the wrapped transport
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Write all of `data`, retrying short writes
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), FramingError<T::Error>> {
+        let mut written = 0;
+        while written < data.len() {
+            let n = self
+                .inner
+                .write(&data[written..])
+                .await
+                .map_err(FramingError::Transport)?;
+            if n == 0 {
+                return Err(FramingError::UnexpectedEof);
+            }
+            written += n;
+        }
+        Ok(())
+    }
+
+    /// Fill `buf` from the wrapped transport, see [`AsyncTransport::read_exact`]
+    async fn fill(&mut self, buf: &mut [u8]) -> Result<usize, FramingError<T::Error>> {
+        self.inner
+            .read_exact(buf)
+            .await
+            .map_err(FramingError::Transport)
+    }
+
+    /// Read and discard `len` bytes, keeping the stream aligned on frames
+    async fn skip(&mut self, mut len: usize) -> Result<(), FramingError<T::Error>> {
+        let mut scratch = [0u8; 32];
+        while len > 0 {
+            let chunk = &mut scratch[..len.min(32)];
+            let n = chunk.len();
+            if self.fill(chunk).await? < n {
+                return Err(FramingError::UnexpectedEof);
+            }
+            len -= n;
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsyncTransport> AsyncTransport for FramedTransport<T> {
+    type Error = FramingError<T::Error>;
+
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        let len = u16::try_from(data.len()).map_err(|_| FramingError::FrameTooLarge)?;
+
+        self.write_all(&len.to_le_bytes()).await?;
+        self.write_all(data).await?;
+
+        Ok(data.len())
+    }
+
+    /// Read one frame into `buffer`
+    ///
+    /// Returns `Ok(0)` if no data is available before the frame starts. A
+    /// frame longer than `buffer` is skipped and reported as
+    /// `FramingError::BufferTooSmall`, so the next read starts on a frame.
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut prefix = [0u8; 2];
+        match self.fill(&mut prefix).await? {
+            0 => return Ok(0),
+            2 => {}
+            _ => return Err(FramingError::UnexpectedEof),
+        }
+
+        let len = u16::from_le_bytes(prefix) as usize;
+        let Some(frame) = buffer.get_mut(..len) else {
+            self.skip(len).await?;
+            return Err(FramingError::BufferTooSmall { needed: len });
+        };
+        if self.fill(frame).await? < len {
+            return Err(FramingError::UnexpectedEof);
+        }
+
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().await.map_err(FramingError::Transport)
+    }
+}
+
+/// COBS framing
+///
+/// Each packet is COBS-encoded and terminated with a `0x00` delimiter. Since
+/// the delimiter never appears inside an encoded frame, the reader
+/// resynchronizes on the next delimiter after corruption or dropped bytes.
+///
+/// `N` sizes the internal receive buffer and must hold the largest encoded
+/// frame (packet length plus one byte per 254 bytes of payload, plus one).
+///
+/// # Example
+///
+/// ```ignore
+/// let transport = CobsTransport::<_, 300>::new(uart);
+/// let mut ble = Ble::new(transport).await?;
+/// ```
+pub struct CobsTransport<T: AsyncTransport, const N: usize> {
+    inner: T,
+    rx: [u8; N],
+    rx_len: usize,
+    /// Set after an oversized frame until its delimiter has been skipped
+    discarding: bool,
+}
+
+impl<T: AsyncTransport, const N: usize> CobsTransport<T, N> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            rx: [0u8; N],
+            rx_len: 0,
+            discarding: false,
+        }
+    }
+
+    /// Recover the wrapped transport
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), FramingError<T::Error>> {
+        let mut written = 0;
+        while written < data.len() {
+            let n = self
+                .inner
+                .write(&data[written..])
+                .await
+                .map_err(FramingError::Transport)?;
+            if n == 0 {
+                return Err(FramingError::UnexpectedEof);
+            }
+            written += n;
+        }
+        Ok(())
+    }
+
+    /// Remove the first `len` bytes from the receive buffer
+    fn consume(&mut self, len: usize) {
+        self.rx.copy_within(len..self.rx_len, 0);
+        self.rx_len -= len;
+    }
+}
+
+impl<T: AsyncTransport, const N: usize> AsyncTransport for CobsTransport<T, N> {
+    type Error = FramingError<T::Error>;
+
+    /// Encode and write one frame
+    ///
+    /// The encoding is streamed block by block, so no transmit buffer is needed.
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        let mut start = 0;
+        loop {
+            let mut end = start;
+            while end < data.len() && data[end] != 0 && end - start < 254 {
+                end += 1;
+            }
+
+            let code = (end - start + 1) as u8;
+            self.write_all(&[code]).await?;
+            self.write_all(&data[start..end]).await?;
+
+            if end == data.len() {
+                break;
+            }
+            // A full block has no implied zero; otherwise skip the zero at `end`
+            start = if code == 0xFF { end } else { end + 1 };
+        }
+
+        self.write_all(&[0x00]).await?;
+        Ok(data.len())
+    }
+
+    /// Read and decode one frame into `buffer`
+    ///
+    /// Returns `Ok(0)` if the transport has no more data before a complete
+    /// frame arrives; the partial frame is kept for the next call.
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        loop {
+            if let Some(pos) = self.rx[..self.rx_len].iter().position(|&b| b == 0) {
+                let discarding = core::mem::replace(&mut self.discarding, false);
+                let result = cobs_decode(&self.rx[..pos], buffer);
+                self.consume(pos + 1);
+
+                // Skip empty frames and the tail of an oversized one
+                if discarding || pos == 0 {
+                    continue;
+                }
+                return result;
+            }
+
+            if self.rx_len == N {
+                // No delimiter in a full buffer: drop it and skip to the next one
+                self.rx_len = 0;
+                if !core::mem::replace(&mut self.discarding, true) {
+                    return Err(FramingError::FrameTooLarge);
+                }
+                continue;
+            }
+
+            let n = self
+                .inner
+                .read(&mut self.rx[self.rx_len..])
+                .await
+                .map_err(FramingError::Transport)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.rx_len += n;
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().await.map_err(FramingError::Transport)
+    }
+}
+
+/// Decode a COBS frame (without its delimiter) into `dst`
+fn cobs_decode<E>(src: &[u8], dst: &mut [u8]) -> Result<usize, FramingError<E>> {
+    let mut i = 0;
+    let mut o = 0;
+
+    while i < src.len() {
+        let code = src[i] as usize;
+        i += 1;
+
+        let block = src.get(i..i + code - 1).ok_or(FramingError::InvalidFrame)?;
+        dst.get_mut(o..o + block.len())
+            .ok_or(FramingError::FrameTooLarge)?
+            .copy_from_slice(block);
+        i += block.len();
+        o += block.len();
+
+        if code != 0xFF && i < src.len() {
+            *dst.get_mut(o).ok_or(FramingError::FrameTooLarge)? = 0;
+            o += 1;
+        }
+    }
+
+    Ok(o)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_stream::stream;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_read_exact_joins_partial_reads() {
+        let mut transport = stream(&[1, 2, 3, 4, 5], 2);
+        let mut buf = [0u8; 4];
+        assert_eq!(block_on(transport.read_exact(&mut buf)).unwrap(), 4);
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        // Only one byte left before the stream ends
+        assert_eq!(block_on(transport.read_exact(&mut buf)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_write_prefixes_length() {
+        let mut framed = FramedTransport::new(stream(&[], 1));
+        block_on(framed.write(&[0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6])).unwrap();
+
+        assert_eq!(
+            framed.into_inner().tx,
+            [0x06, 0x00, 0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]
+        );
+    }
+
+    #[test]
+    fn test_read_reassembles_partial_reads() {
+        // Two frames delivered one byte at a time
+        let rx = [0x02, 0x00, 0xAA, 0xBB, 0x01, 0x00, 0xCC];
+        let mut framed = FramedTransport::new(stream(&rx, 1));
+        let mut buf = [0u8; 16];
+
+        assert_eq!(block_on(framed.read(&mut buf)).unwrap(), 2);
+        assert_eq!(&buf[..2], &[0xAA, 0xBB]);
+        assert_eq!(block_on(framed.read(&mut buf)).unwrap(), 1);
+        assert_eq!(buf[0], 0xCC);
+        assert_eq!(block_on(framed.read(&mut buf)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut framed = FramedTransport::new(stream(&[0x04, 0x00, 0xAA], 8));
+        let mut buf = [0u8; 16];
+        assert!(matches!(
+            block_on(framed.read(&mut buf)),
+            Err(FramingError::UnexpectedEof)
+        ));
+
+        let rx = [0x04, 0x00, 0x01, 0x02, 0x03, 0x04, 0x01, 0x00, 0xCC];
+        let mut framed = FramedTransport::new(stream(&rx, 8));
+        let mut small = [0u8; 2];
+        let err = block_on(framed.read(&mut small)).unwrap_err();
+        assert!(matches!(err, FramingError::BufferTooSmall { needed: 4 }));
+        assert_eq!(err.needed_len(), Some(4));
+
+        // The oversized frame was skipped, so the next one is read intact
+        assert_eq!(block_on(framed.read(&mut small)).unwrap(), 1);
+        assert_eq!(small[0], 0xCC);
+    }
+
+    #[test]
+    fn test_cobs_encoding() {
+        let mut cobs = CobsTransport::<_, 16>::new(stream(&[], 8));
+        block_on(cobs.write(&[0x11, 0x00, 0x00, 0x22, 0x33])).unwrap();
+        block_on(cobs.write(&[0x44, 0x00])).unwrap();
+
+        assert_eq!(
+            cobs.into_inner().tx,
+            [
+                0x02, 0x11, 0x01, 0x03, 0x22, 0x33, 0x00, 0x02, 0x44, 0x01, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cobs_round_trip() {
+        let packet = [0x80, 0x00, 0xFF, 0x00, 0x00, 0x18, 0x1C, 0x18, 0x1C, 0xF6];
+        let long: Vec<u8> = (1..=255u8).chain(0..5).collect();
+
+        let mut writer = CobsTransport::<_, 300>::new(stream(&[], 8));
+        block_on(writer.write(&packet)).unwrap();
+        block_on(writer.write(&long)).unwrap();
+
+        let mut reader = CobsTransport::<_, 300>::new(stream(&writer.into_inner().tx, 3));
+        let mut buf = [0u8; 300];
+        let len = block_on(reader.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &packet);
+        let len = block_on(reader.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &long[..]);
+        assert_eq!(block_on(reader.read(&mut buf)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cobs_resyncs_after_corruption() {
+        let mut writer = CobsTransport::<_, 32>::new(stream(&[], 8));
+        block_on(writer.write(&[0x01, 0x02, 0x03])).unwrap();
+        block_on(writer.write(&[0x04, 0x05])).unwrap();
+
+        // Corrupt the first code byte so it claims more data than the frame holds
+        let mut rx = writer.into_inner().tx;
+        rx[0] = 0x7F;
+
+        let mut reader = CobsTransport::<_, 32>::new(stream(&rx, 2));
+        let mut buf = [0u8; 32];
+        assert!(matches!(
+            block_on(reader.read(&mut buf)),
+            Err(FramingError::InvalidFrame)
+        ));
+        let len = block_on(reader.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[0x04, 0x05]);
+    }
+
+    #[test]
+    fn test_cobs_skips_oversized_frame() {
+        let mut rx = [0x09, 1, 2, 3, 4, 5, 6, 7, 8, 0x00].to_vec();
+        rx.extend_from_slice(&[0x02, 0xAA, 0x00]);
+
+        let mut reader = CobsTransport::<_, 4>::new(stream(&rx, 4));
+        let mut buf = [0u8; 16];
+        assert!(matches!(
+            block_on(reader.read(&mut buf)),
+            Err(FramingError::FrameTooLarge)
+        ));
+        let len = block_on(reader.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[0xAA]);
+    }
+}
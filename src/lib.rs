@@ -1,20 +1,69 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+#[cfg(all(feature = "testing", not(test)))]
+extern crate std;
+
 pub mod ble;
+mod buffered;
+mod framing;
+mod observer;
 #[doc(hidden)]
 pub mod packet;
+#[cfg(feature = "embassy-sync")]
+mod shared;
+#[cfg(test)]
+mod test_stream;
+#[cfg(feature = "testing")]
+pub mod test_util;
+mod timeout;
 mod transport;
+pub mod version;
 
+pub use buffered::BufferedTransport;
+pub use framing::{CobsTransport, FramedTransport, FramingError};
+pub use observer::PacketObserver;
+#[cfg(feature = "embassy-sync")]
+pub use shared::{BleGuard, SharedBle};
+pub use timeout::with_timeout;
 pub use transport::{AsyncTransport, TransportError};
 
-use packet::{CborError, PacketBuilder};
+use core::future::Future;
+
+use ble::{ConnHandle, Event, decode_event};
+
+use packet::{CborError, HEADER_LEN, PacketBuilder, PacketHeader, PacketType};
+use version::{PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN};
 
 /// RPC client errors
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum RpcError {
+    /// The transport failed; the cause is kept until `take_transport_error`
     Transport,
     Cbor(CborError),
     InvalidResponse,
+    /// No response arrived before the delay passed to `with_timeout` elapsed
     Timeout,
+    /// The remote replied with an error report carrying this error code
+    Remote(i32),
+    /// Every context ID is in use by an outstanding command
+    NoFreeContext,
+    /// The transport had no data, as opposed to delivering a malformed packet
+    NoData,
+    /// The response needs a receive buffer of `needed` bytes; raise `RX`
+    ResponseTooLarge {
+        needed: usize,
+    },
+    /// The connection handle is not one the remote reported as connected
+    InvalidHandle,
+    /// The command's group has no ID yet; run `init` first
+    GroupNotRegistered,
+    /// An argument is outside the range the command can encode
+    InvalidArgument,
+    /// The remote does not support the protocol version this crate speaks
+    VersionMismatch {
+        min: u8,
+        max: u8,
+    },
 }
 
 impl core::fmt::Display for RpcError {
@@ -24,6 +73,31 @@ impl core::fmt::Display for RpcError {
             RpcError::Cbor(e) => write!(f, "CBOR error: {}", e),
             RpcError::InvalidResponse => write!(f, "Invalid response"),
             RpcError::Timeout => write!(f, "Timeout"),
+            RpcError::Remote(code) => write!(f, "Remote error: {}", code),
+            RpcError::NoFreeContext => write!(f, "No free context ID"),
+            RpcError::NoData => write!(f, "No data"),
+            RpcError::InvalidHandle => write!(f, "Invalid connection handle"),
+            RpcError::GroupNotRegistered => write!(f, "Group not registered"),
+            RpcError::InvalidArgument => write!(f, "Invalid argument"),
+            RpcError::ResponseTooLarge { needed } => {
+                write!(f, "Response too large ({} bytes needed)", needed)
+            }
+            RpcError::VersionMismatch { min, max } => {
+                write!(
+                    f,
+                    "Unsupported protocol version (remote supports {}..={})",
+                    min, max
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for RpcError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            RpcError::Cbor(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -34,108 +108,1489 @@ impl From<CborError> for RpcError {
     }
 }
 
+/// Number of times `init_with_retry` resends the init packets
+const INIT_RETRIES: usize = 3;
+
+/// Packets `init` reads while waiting for both init responses before giving up
+const INIT_MAX_PACKETS: usize = 8;
+
+/// Group ID placeholder used until init assigns the real one
+const GROUP_ID_UNASSIGNED: u8 = 0xFF;
+
+/// Local ID of the bt_rpc group, announced in its init packet
+const LOCAL_BT_RPC_GROUP: u8 = 0x00;
+
+/// Local ID of the rpc_utils group, announced in its init packet
+const LOCAL_RPC_UTILS_GROUP: u8 = 0x01;
+
+/// Number of live connections tracked for handle validation
+///
+/// Matches the largest `CONFIG_BT_MAX_CONN` a typical network core is built with.
+const MAX_CONNS: usize = 8;
+
+/// Result of the RPC handshake
+///
+/// Holds the group IDs the remote assigned during `init` and the protocol
+/// version in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitInfo {
+    pub bt_rpc_group_id: u8,
+    pub rpc_utils_group_id: u8,
+    pub version: u8,
+}
+
 /// NRF RPC Client
 ///
 /// Generic over a transport. The transport can be any implementation
-/// of the AsyncTransport trait (e.g., UART, IPC, USB).
-pub(crate) struct RpcClient<T: AsyncTransport> {
+/// of the AsyncTransport trait (e.g., UART, IPC, USB). `RX` is the size of
+/// the stack buffer each call receives its response into.
+pub(crate) struct RpcClient<T: AsyncTransport, const RX: usize = 256> {
     transport: T,
     bt_rpc_group_id: u8,
     rpc_utils_group_id: u8,
-    context_id: u8,
+    /// Protocol version agreed with the remote during `init`
+    version: u8,
+    /// Bitmap of source context IDs in use by outstanding commands
+    context_ids: u64,
+    observer: Option<&'static dyn PacketObserver>,
+    event_handler: Option<fn(Event<'_>)>,
+    /// Whether `check_conn` rejects handles missing from `conns`
+    validate_conns: bool,
+    /// Handles reported by `Connected` events and not yet disconnected
+    conns: [Option<ConnHandle>; MAX_CONNS],
+    /// Cause of the most recent `RpcError::Transport`
+    transport_error: Option<T::Error>,
 }
 
-impl<T: AsyncTransport> RpcClient<T> {
+impl<T: AsyncTransport, const RX: usize> RpcClient<T, RX> {
     pub fn new(transport: T) -> Self {
+        Self::from_parts(transport, GROUP_ID_UNASSIGNED, GROUP_ID_UNASSIGNED)
+    }
+
+    /// Create a client for a remote whose group IDs are already known
+    ///
+    /// No handshake is performed, so `init` must not be needed.
+    pub fn from_parts(transport: T, bt_rpc_group_id: u8, rpc_utils_group_id: u8) -> Self {
         Self {
             transport,
-            bt_rpc_group_id: 0xFF,
-            rpc_utils_group_id: 0xFF,
-            context_id: 0,
+            bt_rpc_group_id,
+            rpc_utils_group_id,
+            version: PROTOCOL_VERSION_MAX,
+            context_ids: 0,
+            observer: None,
+            event_handler: None,
+            validate_conns: false,
+            conns: [None; MAX_CONNS],
+            transport_error: None,
+        }
+    }
+
+    /// Recover the wrapped transport
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Take the transport error behind the last `RpcError::Transport`
+    ///
+    /// `RpcError` is not generic over the transport, so the concrete error is
+    /// kept here instead. Returns `None` if no transport error has occurred
+    /// since the last call.
+    pub fn take_transport_error(&mut self) -> Option<T::Error> {
+        self.transport_error.take()
+    }
+
+    /// Report every packet sent or received to `observer`
+    pub fn with_observer(mut self, observer: &'static dyn PacketObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Reject connection handles that are not currently connected
+    pub fn with_conn_validation(mut self) -> Self {
+        self.validate_conns = true;
+        self
+    }
+
+    /// Pass every event received from now on to `handler`
+    pub fn set_event_handler(&mut self, handler: fn(Event<'_>)) {
+        self.event_handler = Some(handler);
+    }
+
+    /// Handle every event the remote has already queued
+    ///
+    /// Each event is acknowledged and passed to the event handler. Returns once
+    /// a read comes back empty, so the transport's `read` must not block when
+    /// no data is pending. Late responses and error reports for abandoned
+    /// commands are dropped and their context IDs released, and init packets
+    /// from the remote are answered by `handle_incoming_init`.
+    pub async fn poll_events(&mut self) -> Result<(), RpcError> {
+        let mut buf = [0u8; RX];
+        loop {
+            let len = match self.receive_packet(&mut buf).await {
+                Ok(len) => len,
+                Err(RpcError::NoData) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let header = PacketHeader::parse(&buf[..len]).map_err(|_| RpcError::InvalidResponse)?;
+
+            let type_byte = buf[0];
+            if PacketType::is_event(type_byte) {
+                self.handle_event(&header, &buf[HEADER_LEN..len]).await?;
+            } else if PacketType::is_response(type_byte) || PacketType::is_error_report(type_byte) {
+                self.free_context_id(header.dst_ctx_id);
+            } else if PacketType::is_init(type_byte) {
+                self.handle_incoming_init(&buf[..len]).await?;
+            } else {
+                return Err(RpcError::InvalidResponse);
+            }
         }
     }
 
     /// Initialize RPC client by registering bt_rpc and rpc_utils groups
-    pub async fn init(&mut self) -> Result<(), RpcError> {
-        let bt_rpc_init = PacketBuilder::<64>::new().init(0x00, "bt_rpc");
+    ///
+    /// Returns the group IDs negotiated with the remote.
+    pub async fn init(&mut self) -> Result<InitInfo, RpcError> {
+        self.send_init_packets().await?;
+        self.receive_init_responses().await
+    }
+
+    /// Initialize like `init`, resending the init packets if the remote does
+    /// not answer in time
+    ///
+    /// `delay` is called once per attempt and must return a fresh future that
+    /// completes when that attempt should be abandoned, e.g.
+    /// `|| Timer::after(Duration::from_millis(100))`. After `INIT_RETRIES`
+    /// resends without an answer, `RpcError::Timeout` is returned.
+    pub async fn init_with_retry<D: Future>(
+        &mut self,
+        mut delay: impl FnMut() -> D,
+    ) -> Result<InitInfo, RpcError> {
+        let mut attempts = 0;
+        loop {
+            self.send_init_packets().await?;
+            match with_timeout(delay(), self.receive_init_responses()).await {
+                Err(RpcError::Timeout) if attempts < INIT_RETRIES => attempts += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Read and discard everything the transport has buffered
+    ///
+    /// Reads into `scratch` until the transport reports no data, returning
+    /// the number of bytes discarded. Run before `init` after a reset, when
+    /// the receive FIFO may still hold partial packets from before. Packets
+    /// too large for `scratch` are counted if the transport skips them.
+    ///
+    /// A transport whose `read` waits for data never reports the link idle;
+    /// wrap the call in `with_timeout` for those.
+    pub async fn drain_rx(&mut self, scratch: &mut [u8]) -> Result<usize, RpcError> {
+        let mut drained = 0;
+        loop {
+            match self.receive_packet(scratch).await {
+                Ok(len) => drained += len,
+                Err(RpcError::NoData) => return Ok(drained),
+                Err(RpcError::ResponseTooLarge { needed }) => drained += needed,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Answer an init packet sent by the remote, for the responder role
+    ///
+    /// `bytes` is the received init packet. The requested group must be one
+    /// this crate serves (`bt_rpc` or `rpc_utils`), otherwise
+    /// `RpcError::GroupNotRegistered` is returned and nothing is sent. The
+    /// remote's ID for the group is recorded, so later packets reach it.
+    pub async fn handle_incoming_init(&mut self, bytes: &[u8]) -> Result<(), RpcError> {
+        let (name, remote_group_id) = parse_init_request(bytes)?;
+
+        let local_group_id = match name {
+            b"bt_rpc" => LOCAL_BT_RPC_GROUP,
+            b"rpc_utils" => LOCAL_RPC_UTILS_GROUP,
+            _ => return Err(RpcError::GroupNotRegistered),
+        };
+
+        // Same layout as the init responses `parse_init_response` accepts
+        let reply =
+            PacketBuilder::<64>::new().init_response(remote_group_id, local_group_id, name)?;
+        self.send_packet(reply.as_slice()).await?;
+
+        match local_group_id {
+            LOCAL_BT_RPC_GROUP => self.bt_rpc_group_id = remote_group_id,
+            _ => self.rpc_utils_group_id = remote_group_id,
+        }
+        Ok(())
+    }
+
+    async fn send_init_packets(&mut self) -> Result<(), RpcError> {
+        let bt_rpc_init = PacketBuilder::<64>::new().init(LOCAL_BT_RPC_GROUP, "bt_rpc")?;
         self.send_packet(bt_rpc_init.as_slice()).await?;
 
-        let rpc_utils_init = PacketBuilder::<64>::new().init(0x01, "rpc_utils");
-        self.send_packet(rpc_utils_init.as_slice()).await?;
+        let rpc_utils_init = PacketBuilder::<64>::new().init(LOCAL_RPC_UTILS_GROUP, "rpc_utils")?;
+        self.send_packet(rpc_utils_init.as_slice()).await
+    }
+
+    /// Receive the init responses for bt_rpc and rpc_utils
+    ///
+    /// The remote may answer in either order, so each group ID is assigned by
+    /// the group name in the response. Other packets and responses for unknown
+    /// groups are skipped. If either group is still unassigned after
+    /// `INIT_MAX_PACKETS` packets, `RpcError::GroupNotRegistered` is returned.
+    async fn receive_init_responses(&mut self) -> Result<InitInfo, RpcError> {
+        let mut response_buf = [0u8; RX];
+        self.bt_rpc_group_id = GROUP_ID_UNASSIGNED;
+        self.rpc_utils_group_id = GROUP_ID_UNASSIGNED;
 
-        let mut response_buf = [0u8; 256];
+        for _ in 0..INIT_MAX_PACKETS {
+            if self.groups_assigned() {
+                return Ok(self.init_info());
+            }
 
-        let len = self.receive_packet(&mut response_buf).await?;
-        if len >= 5 && response_buf[0] == 0x04 {
-            self.bt_rpc_group_id = response_buf[4];
+            let len = self.receive_packet(&mut response_buf).await?;
+            let Some(response) = parse_init_response(&response_buf[..len])? else {
+                continue;
+            };
+            match response.group_name {
+                b"bt_rpc" => self.bt_rpc_group_id = response.group_id,
+                b"rpc_utils" => self.rpc_utils_group_id = response.group_id,
+                _ => continue,
+            }
+            self.version = response.version;
         }
 
-        let len = self.receive_packet(&mut response_buf).await?;
-        if len >= 5 && response_buf[0] == 0x04 {
-            self.rpc_utils_group_id = response_buf[4];
+        if !self.groups_assigned() {
+            return Err(RpcError::GroupNotRegistered);
         }
+        Ok(self.init_info())
+    }
 
-        Ok(())
+    /// Whether the remote has assigned IDs to both bt_rpc and rpc_utils
+    fn groups_assigned(&self) -> bool {
+        self.bt_rpc_group_id != GROUP_ID_UNASSIGNED
+            && self.rpc_utils_group_id != GROUP_ID_UNASSIGNED
+    }
+
+    /// Group IDs and protocol version currently in use
+    pub(crate) fn init_info(&self) -> InitInfo {
+        InitInfo {
+            bt_rpc_group_id: self.bt_rpc_group_id,
+            rpc_utils_group_id: self.rpc_utils_group_id,
+            version: self.version,
+        }
     }
 
     // Accessor methods for internal use by command modules
+
+    /// Context ID the next command will be sent in
+    ///
+    /// `send_command` allocates the context and stamps it into the packet, so
+    /// this is only used to fill in the header while building.
     pub(crate) fn context_id(&self) -> u8 {
-        self.context_id
+        (!self.context_ids).trailing_zeros().min(63) as u8
+    }
+
+    /// Allocate an unused source context ID
+    ///
+    /// Returns `None` when all 64 context IDs are in use.
+    pub(crate) fn alloc_context_id(&mut self) -> Option<u8> {
+        let id = (!self.context_ids).trailing_zeros();
+        if id >= u64::BITS {
+            return None;
+        }
+        self.context_ids |= 1 << id;
+        Some(id as u8)
+    }
+
+    /// Release a context ID obtained from `alloc_context_id`
+    pub(crate) fn free_context_id(&mut self, id: u8) {
+        if u32::from(id) < u64::BITS {
+            self.context_ids &= !(1 << id);
+        }
     }
 
     pub(crate) fn bt_rpc_group_id(&self) -> u8 {
         self.bt_rpc_group_id
     }
 
+    pub(crate) fn rpc_utils_group_id(&self) -> u8 {
+        self.rpc_utils_group_id
+    }
+
+    /// Start a command packet for `cmd_id` in the group `group_id`
+    ///
+    /// Every group client builds its commands through this, passing the ID
+    /// the remote assigned to its group. The source context ID is filled in
+    /// when the command is sent.
+    pub(crate) fn command_in_group<const N: usize>(
+        &self,
+        group_id: u8,
+        cmd_id: u8,
+    ) -> PacketBuilder<N> {
+        PacketBuilder::<N>::new().command(self.context_id(), cmd_id, 0xFF, group_id, group_id)
+    }
+
     pub(crate) async fn send_packet(&mut self, packet: &[u8]) -> Result<(), RpcError> {
-        self.transport
-            .write(packet)
-            .await
-            .map_err(|_| RpcError::Transport)?;
+        if let Some(observer) = self.observer {
+            observer.on_write(packet);
+        }
+
+        if let Err(e) = self.transport.write(packet).await {
+            return Err(self.transport_failed(e));
+        }
+        if let Err(e) = self.transport.flush().await {
+            return Err(self.transport_failed(e));
+        }
         Ok(())
     }
 
+    /// Keep `e` for `take_transport_error` and map it to an `RpcError`
+    fn transport_failed(&mut self, e: T::Error) -> RpcError {
+        if let Some(needed) = e.needed_len() {
+            return RpcError::ResponseTooLarge { needed };
+        }
+        self.transport_error = Some(e);
+        RpcError::Transport
+    }
+
+    /// Receive a single packet into `output`
+    ///
+    /// A zero-length read means the link is idle and is reported as
+    /// `RpcError::NoData`, so it can't be mistaken for a truncated packet. A
+    /// packet the transport knows is longer than `output` is reported as
+    /// `RpcError::ResponseTooLarge`.
     pub(crate) async fn receive_packet(&mut self, output: &mut [u8]) -> Result<usize, RpcError> {
-        self.transport
-            .read(output)
-            .await
-            .map_err(|_| RpcError::Transport)
+        let len = match self.transport.read(output).await {
+            Ok(len) => len,
+            Err(e) => return Err(self.transport_failed(e)),
+        };
+        if len == 0 {
+            return Err(RpcError::NoData);
+        }
+
+        if let Some(observer) = self.observer {
+            observer.on_read(&output[..len]);
+        }
+        Ok(len)
+    }
+
+    pub(crate) async fn send_command(&mut self, packet: &mut [u8]) -> Result<i32, RpcError> {
+        let mut response_buf = [0u8; RX];
+        let payload = self.send_command_raw(packet, &mut response_buf).await?;
+        self.decode_i32_response(payload)
+    }
+
+    /// Send a command whose response is a CBOR bool
+    pub(crate) async fn send_command_bool(&mut self, packet: &mut [u8]) -> Result<bool, RpcError> {
+        let mut response_buf = [0u8; RX];
+        let payload = self.send_command_raw(packet, &mut response_buf).await?;
+        self.decode_bool_response(payload)
+    }
+
+    /// Send a command for a function returning `void`
+    pub(crate) async fn send_command_void(&mut self, packet: &mut [u8]) -> Result<(), RpcError> {
+        let mut response_buf = [0u8; RX];
+        let payload = self.send_command_raw(packet, &mut response_buf).await?;
+        self.decode_void_response(payload)
     }
 
-    pub(crate) async fn send_command(&mut self, packet: &[u8]) -> Result<i32, RpcError> {
-        self.send_packet(packet).await?;
+    /// Send a command and return its undecoded response payload
+    ///
+    /// The whole response packet is received into `out`, so it needs room for
+    /// the header as well. The payload is then moved to the front of `out` and
+    /// returned as a slice of it.
+    pub(crate) async fn send_command_raw<'b>(
+        &mut self,
+        packet: &mut [u8],
+        out: &'b mut [u8],
+    ) -> Result<&'b [u8], RpcError> {
+        let len = self.transact(packet, out).await?;
 
-        let mut response_buf = [0u8; 256];
-        let len = self.receive_packet(&mut response_buf).await?;
+        out.copy_within(HEADER_LEN..len, 0);
+        Ok(&out[..len - HEADER_LEN])
+    }
+
+    /// Send a command whose response carries a CBOR text string
+    ///
+    /// The string is copied into `out` and returned as a slice of it.
+    pub(crate) async fn send_command_str<'b>(
+        &mut self,
+        packet: &mut [u8],
+        out: &'b mut [u8],
+    ) -> Result<&'b str, RpcError> {
+        let mut response_buf = [0u8; RX];
+        let len = self.transact(packet, &mut response_buf).await?;
+
+        let payload = &response_buf[HEADER_LEN..len];
+        self.decode_str_response(payload, out)
+    }
+
+    /// Send a command and decode its response payload with `decode`
+    ///
+    /// Used by commands whose response is more than a single value.
+    pub(crate) async fn send_command_with<R>(
+        &mut self,
+        packet: &mut [u8],
+        decode: impl FnOnce(&[u8]) -> Result<R, RpcError>,
+    ) -> Result<R, RpcError> {
+        let mut response_buf = [0u8; RX];
+        let len = self.transact(packet, &mut response_buf).await?;
 
-        if len < 5 {
+        decode(&response_buf[HEADER_LEN..len])
+    }
+
+    /// Send a command in a freshly allocated context and wait for its response
+    ///
+    /// The context ID is released once the response (or an error) arrives. A
+    /// packet too short to hold a header is a framing error and fails with
+    /// `RpcError::InvalidResponse` before anything is sent.
+    async fn transact(&mut self, packet: &mut [u8], output: &mut [u8]) -> Result<usize, RpcError> {
+        if packet.len() < HEADER_LEN {
             return Err(RpcError::InvalidResponse);
         }
+        let header = PacketHeader::parse(packet)?;
+        if header.dst_group_id == GROUP_ID_UNASSIGNED {
+            return Err(RpcError::GroupNotRegistered);
+        }
+        let ctx_id = self.alloc_context_id().ok_or(RpcError::NoFreeContext)?;
+        if let Some(type_byte) = packet.first_mut() {
+            *type_byte = PacketType::Command.to_byte(ctx_id);
+        }
+
+        let result = match self.send_packet(packet).await {
+            Ok(()) => self.receive_response(ctx_id, output).await,
+            Err(e) => Err(e),
+        };
+
+        self.free_context_id(ctx_id);
+        result
+    }
+
+    /// Receive packets until the response for context `ctx_id` arrives
+    ///
+    /// Returns the total packet length, so the payload is `output[HEADER_LEN..len]`.
+    /// An error report from the remote is surfaced as `RpcError::Remote`.
+    /// Events received while waiting are acknowledged and passed to the event
+    /// handler.
+    ///
+    /// Responses and error reports addressed to any other context are dropped.
+    /// Only one command is in flight at a time, so such a reply can only belong
+    /// to a command that was abandoned before it arrived (e.g. by
+    /// `with_timeout`). That command never released its context ID, so it is
+    /// released here.
+    async fn receive_response(&mut self, ctx_id: u8, output: &mut [u8]) -> Result<usize, RpcError> {
+        loop {
+            let len = self.receive_packet(output).await?;
+            let header =
+                PacketHeader::parse(&output[..len]).map_err(|_| RpcError::InvalidResponse)?;
+
+            let type_byte = output[0];
+            if PacketType::is_response(type_byte) {
+                if header.dst_ctx_id == ctx_id {
+                    return Ok(len);
+                }
+                self.free_context_id(header.dst_ctx_id);
+            } else if PacketType::is_error_report(type_byte) {
+                if header.dst_ctx_id != ctx_id {
+                    self.free_context_id(header.dst_ctx_id);
+                    continue;
+                }
+                // Error report payload is a little-endian 32-bit error code
+                let code = output[HEADER_LEN..len]
+                    .first_chunk::<4>()
+                    .ok_or(RpcError::InvalidResponse)?;
+                return Err(RpcError::Remote(i32::from_le_bytes(*code)));
+            } else if PacketType::is_event(type_byte) {
+                self.handle_event(&header, &output[HEADER_LEN..len]).await?;
+            } else {
+                return Err(RpcError::InvalidResponse);
+            }
+        }
+    }
 
-        let packet_type = response_buf[0] & 0x7F;
-        if packet_type != 0x01 {
+    /// Wait for an event packet from the remote and acknowledge it
+    ///
+    /// Returns the event header and total packet length, so the payload is
+    /// `output[HEADER_LEN..len]`.
+    pub(crate) async fn receive_event(
+        &mut self,
+        output: &mut [u8],
+    ) -> Result<(PacketHeader, usize), RpcError> {
+        let len = self.receive_packet(output).await?;
+        let header = PacketHeader::parse(&output[..len]).map_err(|_| RpcError::InvalidResponse)?;
+        if !PacketType::is_event(output[0]) {
             return Err(RpcError::InvalidResponse);
         }
 
-        let payload = &response_buf[5..len];
-        self.decode_i32_response(payload)
+        self.send_event_ack(&header).await?;
+        Ok((header, len))
+    }
+
+    /// Wait for the event `evt_id`, handling any other events on the way
+    ///
+    /// The awaited event is acknowledged but not dispatched to the event
+    /// handler. Returns its total packet length, so the payload is
+    /// `output[HEADER_LEN..len]`. Late responses and error reports for
+    /// abandoned commands are dropped and their context IDs released.
+    pub(crate) async fn wait_event(
+        &mut self,
+        evt_id: u8,
+        output: &mut [u8],
+    ) -> Result<usize, RpcError> {
+        loop {
+            let len = self.receive_packet(output).await?;
+            let header =
+                PacketHeader::parse(&output[..len]).map_err(|_| RpcError::InvalidResponse)?;
+
+            let type_byte = output[0];
+            if PacketType::is_event(type_byte) {
+                if header.command_id == evt_id {
+                    self.send_event_ack(&header).await?;
+                    return Ok(len);
+                }
+                self.handle_event(&header, &output[HEADER_LEN..len]).await?;
+            } else if PacketType::is_response(type_byte) || PacketType::is_error_report(type_byte) {
+                self.free_context_id(header.dst_ctx_id);
+            } else {
+                return Err(RpcError::InvalidResponse);
+            }
+        }
+    }
+
+    /// Send the event `evt_id` to the group `group_id` and wait for its ack
+    ///
+    /// `payload` appends the event's CBOR payload to the started packet.
+    /// Events received while waiting are handled as usual. If the ack has not
+    /// arrived when `delay` completes, `RpcError::Timeout` is returned.
+    #[allow(dead_code)] // No group originates events yet
+    pub(crate) async fn send_event<D: Future>(
+        &mut self,
+        group_id: u8,
+        evt_id: u8,
+        payload: impl FnOnce(PacketBuilder<RX>) -> Result<PacketBuilder<RX>, CborError>,
+        delay: D,
+    ) -> Result<(), RpcError> {
+        let packet = payload(PacketBuilder::<RX>::new().event(evt_id, group_id, group_id))?;
+        self.send_packet(packet.as_slice()).await?;
+        with_timeout(delay, self.wait_event_ack(evt_id)).await
+    }
+
+    /// Receive packets until the ack for the event `evt_id` arrives
+    async fn wait_event_ack(&mut self, evt_id: u8) -> Result<(), RpcError> {
+        let mut buf = [0u8; RX];
+        loop {
+            let len = self.receive_packet(&mut buf).await?;
+            let header = PacketHeader::parse(&buf[..len]).map_err(|_| RpcError::InvalidResponse)?;
+
+            let type_byte = buf[0];
+            if PacketType::is_event_ack(type_byte) && header.command_id == evt_id {
+                return Ok(());
+            } else if PacketType::is_event(type_byte) {
+                self.handle_event(&header, &buf[HEADER_LEN..len]).await?;
+            } else if PacketType::is_response(type_byte) || PacketType::is_error_report(type_byte) {
+                self.free_context_id(header.dst_ctx_id);
+            } else {
+                return Err(RpcError::InvalidResponse);
+            }
+        }
+    }
+
+    /// Acknowledge a received event
+    ///
+    /// The remote blocks until the acknowledgment arrives, so this must be sent
+    /// for every event packet.
+    pub(crate) async fn send_event_ack(&mut self, header: &PacketHeader) -> Result<(), RpcError> {
+        let ack = PacketBuilder::<HEADER_LEN>::new().event_ack(
+            header.command_id,
+            header.dst_group_id,
+            header.src_group_id,
+        );
+        self.send_packet(ack.as_slice()).await
+    }
+
+    /// Acknowledge an unsolicited event and dispatch it to the event handler
+    ///
+    /// Connection events also update the set of live handles. Events that
+    /// fail to decode are acknowledged but not dispatched.
+    async fn handle_event(
+        &mut self,
+        header: &PacketHeader,
+        payload: &[u8],
+    ) -> Result<(), RpcError> {
+        self.send_event_ack(header).await?;
+        let Ok(event) = decode_event(header.command_id, payload) else {
+            return Ok(());
+        };
+
+        self.track_conn(&event);
+        if let Some(handler) = self.event_handler {
+            handler(event);
+        }
+        Ok(())
+    }
+
+    /// Record connections opened or closed by `event`
+    ///
+    /// A failed connection (`err != 0`) never becomes live. If more than
+    /// `MAX_CONNS` connections are open the extra ones are not tracked.
+    pub(crate) fn track_conn(&mut self, event: &Event<'_>) {
+        match *event {
+            Event::Connected { conn, err: 0 } => {
+                if !self.conns.contains(&Some(conn))
+                    && let Some(slot) = self.conns.iter_mut().find(|slot| slot.is_none())
+                {
+                    *slot = Some(conn);
+                }
+            }
+            Event::Disconnected { conn, .. } => {
+                for slot in self.conns.iter_mut().filter(|slot| **slot == Some(conn)) {
+                    *slot = None;
+                }
+            }
+            _ => {}
+        }
     }
 
+    /// Check that `conn` is live, if connection validation is enabled
+    pub(crate) fn check_conn(&self, conn: ConnHandle) -> Result<(), RpcError> {
+        if self.validate_conns && !self.conns.contains(&Some(conn)) {
+            return Err(RpcError::InvalidHandle);
+        }
+        Ok(())
+    }
+
+    /// An `int` function's response is the value followed by the terminating null
     fn decode_i32_response(&self, payload: &[u8]) -> Result<i32, RpcError> {
         use minicbor::decode::Decoder;
 
         let mut decoder = Decoder::new(payload);
-        decoder.i32().map_err(|_| RpcError::InvalidResponse)
+        let value = decoder.i32().map_err(|_| RpcError::InvalidResponse)?;
+        decoder.null().map_err(|_| RpcError::InvalidResponse)?;
+        Ok(value)
+    }
+
+    /// A `bool` function's response is the value followed by the terminating null
+    fn decode_bool_response(&self, payload: &[u8]) -> Result<bool, RpcError> {
+        let mut decoder = packet::PacketDecoder::new(payload);
+        let value = decoder.decode_bool()?;
+        decoder.expect_null()?;
+        Ok(value)
+    }
+
+    /// A `void` function's response carries only the terminating null
+    fn decode_void_response(&self, payload: &[u8]) -> Result<(), RpcError> {
+        use minicbor::decode::Decoder;
+
+        let mut decoder = Decoder::new(payload);
+        decoder.null().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    fn decode_str_response<'b>(
+        &self,
+        payload: &[u8],
+        out: &'b mut [u8],
+    ) -> Result<&'b str, RpcError> {
+        let mut decoder = packet::PacketDecoder::new(payload);
+        let value = decoder.decode_str(out)?;
+        decoder.expect_null()?;
+        Ok(value)
+    }
+}
+
+/// Parse an init packet sent by the remote, returning the group name and the
+/// remote's ID for that group
+///
+/// The remote's supported version range must overlap ours, as for
+/// `parse_init_response`.
+fn parse_init_request(packet: &[u8]) -> Result<(&[u8], u8), RpcError> {
+    let header = PacketHeader::parse(packet).map_err(|_| RpcError::InvalidResponse)?;
+    if !PacketType::is_init(packet[0]) {
+        return Err(RpcError::InvalidResponse);
+    }
+
+    // Payload is Max Version, Min Version, then the group name
+    let ([max, min], name) = packet[HEADER_LEN..]
+        .split_first_chunk::<2>()
+        .ok_or(RpcError::InvalidResponse)?;
+    negotiate_version(*min, *max)?;
+
+    Ok((name, header.src_group_id))
+}
+
+/// Pick the highest version in both the remote's range `min..=max` and
+/// `PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX`
+fn negotiate_version(min: u8, max: u8) -> Result<u8, RpcError> {
+    (PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX)
+        .rev()
+        .find(|version| (min..=max).contains(version))
+        .ok_or(RpcError::VersionMismatch { min, max })
+}
+
+/// Init response parsed by `parse_init_response`
+#[derive(Debug, PartialEq, Eq)]
+struct InitResponse<'a> {
+    group_name: &'a [u8],
+    /// Group ID the remote assigned to `group_name`
+    group_id: u8,
+    /// Highest protocol version both sides support
+    version: u8,
+}
+
+/// Parse an init response
+///
+/// Returns `Ok(None)` if the packet is not an init packet. The remote's
+/// supported version range must overlap `PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX`.
+fn parse_init_response(packet: &[u8]) -> Result<Option<InitResponse<'_>>, RpcError> {
+    let Ok(header) = PacketHeader::parse(packet) else {
+        return Ok(None);
+    };
+    if !PacketType::is_init(packet[0]) {
+        return Ok(None);
     }
+
+    // Payload is Max Version, Min Version, then the group name
+    let ([max, min], name) = packet[HEADER_LEN..]
+        .split_first_chunk::<2>()
+        .ok_or(RpcError::InvalidResponse)?;
+    let version = negotiate_version(*min, *max)?;
+
+    Ok(Some(InitResponse {
+        group_name: name,
+        group_id: header.dst_group_id,
+        version,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::test_util::{MockTransport, block_on};
+
+    fn mock_client(responses: &[&[u8]]) -> RpcClient<MockTransport> {
+        let transport = MockTransport::new();
+        for response in responses {
+            transport.push_response(response);
+        }
+        RpcClient::new(transport)
+    }
+
     #[test]
     fn test_rpc_error_display() {
         let err = RpcError::Transport;
         assert_eq!(format!("{}", err), "Transport error");
     }
+
+    #[test]
+    fn test_decode_i32_response() {
+        let client = mock_client(&[]);
+
+        assert_eq!(client.decode_i32_response(&[0x00, 0xF6]).unwrap(), 0);
+        assert_eq!(client.decode_i32_response(&[0x2B, 0xF6]).unwrap(), -12);
+        assert!(matches!(
+            client.decode_i32_response(&[0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+        // Missing terminator, and an extra item in its place
+        assert!(matches!(
+            client.decode_i32_response(&[0x00]),
+            Err(RpcError::InvalidResponse)
+        ));
+        assert!(matches!(
+            client.decode_i32_response(&[0x00, 0x01, 0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_decode_bool_and_void_responses() {
+        let client = mock_client(&[]);
+
+        assert!(client.decode_bool_response(&[0xF5, 0xF6]).unwrap());
+        assert!(!client.decode_bool_response(&[0xF4, 0xF6]).unwrap());
+        assert!(matches!(
+            client.decode_bool_response(&[0x00, 0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+        // Extra field before the terminator
+        assert!(matches!(
+            client.decode_bool_response(&[0xF5, 0x01, 0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        client.decode_void_response(&[0xF6]).unwrap();
+        assert!(matches!(
+            client.decode_void_response(&[0x00, 0xF6]),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_decode_str_response() {
+        let client = mock_client(&[]);
+        let payload = [
+            0x69, b'N', b'o', b'r', b'd', b'i', b'c', b'_', b'P', b'S', 0xF6,
+        ];
+
+        let mut out = [0u8; 32];
+        let name = client.decode_str_response(&payload, &mut out).unwrap();
+        assert_eq!(name, "Nordic_PS");
+
+        let mut small = [0u8; 4];
+        assert!(matches!(
+            client.decode_str_response(&payload, &mut small),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        // Extra field before the terminator
+        assert!(matches!(
+            client.decode_str_response(&[0x61, b'a', 0x01, 0xF6], &mut out),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_error_report_maps_to_remote() {
+        // Error report carrying -12 (ENOMEM) in little-endian
+        let mut client = mock_client(&[&[0x03, 0x00, 0x00, 0x00, 0x00, 0xF4, 0xFF, 0xFF, 0xFF]]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert!(matches!(result, Err(RpcError::Remote(-12))));
+    }
+
+    #[test]
+    fn test_stale_error_report_releases_its_context() {
+        // Context 0 belongs to an abandoned command, so this one runs in 1
+        let mut client = mock_client(&[
+            &[0x03, 0x00, 0x00, 0x00, 0x00, 0xF4, 0xFF, 0xFF, 0xFF],
+            &[0x01, 0x00, 0x01, 0x00, 0x00, 0x05, 0xF6],
+        ]);
+        assert_eq!(client.alloc_context_id(), Some(0));
+
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(client.alloc_context_id(), Some(0));
+    }
+
+    #[test]
+    fn test_truncated_error_report_is_invalid() {
+        let mut client = mock_client(&[&[0x03, 0x00, 0x00, 0x00, 0x00, 0xF4]]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert!(matches!(result, Err(RpcError::InvalidResponse)));
+    }
+
+    #[test]
+    fn test_event_is_acked_while_waiting_for_response() {
+        let mut client = mock_client(&[
+            // Event 0x07 from remote group 0x01 to local group 0x00
+            &[0x00, 0x07, 0xFF, 0x01, 0x00, 0xF6],
+            &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6],
+        ]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert_eq!(result.unwrap(), 0);
+
+        let sent = client.transport.sent_packets();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1], [0x02, 0x07, 0xFF, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_context_id_allocation() {
+        let mut client = mock_client(&[]);
+
+        assert_eq!(client.alloc_context_id(), Some(0));
+        assert_eq!(client.alloc_context_id(), Some(1));
+        assert_eq!(client.context_id(), 2);
+
+        client.free_context_id(0);
+        assert_eq!(client.alloc_context_id(), Some(0));
+
+        for _ in 2..64 {
+            assert!(client.alloc_context_id().is_some());
+        }
+        assert_eq!(client.alloc_context_id(), None);
+    }
+
+    #[test]
+    fn test_send_command_releases_context() {
+        let mut client = mock_client(&[&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF6]]);
+        client.alloc_context_id();
+
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert_eq!(result.unwrap(), 0);
+
+        // Sent in the next free context, which is released afterwards
+        assert_eq!(client.transport.sent_packets()[0][0], 0x81);
+        assert_eq!(client.context_id(), 1);
+    }
+
+    #[test]
+    fn test_drain_rx_discards_stale_packets() {
+        let mut client = mock_client(&[&[0x01, 0x02, 0x03], &[0x04]]);
+        let mut scratch = [0u8; 8];
+
+        assert_eq!(block_on(client.drain_rx(&mut scratch)).unwrap(), 4);
+        assert_eq!(block_on(client.drain_rx(&mut scratch)).unwrap(), 0);
+        assert!(client.transport.sent_packets().is_empty());
+    }
+
+    #[test]
+    fn test_init_accepts_supported_version() {
+        let mut client = mock_client(&[
+            &[
+                0x04, 0x00, 0xFF, 0x00, 0x02, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+            ],
+            &[
+                0x04, 0x00, 0xFF, 0x01, 0x03, 0x01, 0x00, b'r', b'p', b'c', b'_', b'u', b't', b'i',
+                b'l', b's',
+            ],
+        ]);
+        let info = block_on(client.init()).unwrap();
+
+        assert_eq!(client.bt_rpc_group_id, 0x02);
+        assert_eq!(client.rpc_utils_group_id, 0x03);
+        assert_eq!(
+            info,
+            InitInfo {
+                bt_rpc_group_id: 0x02,
+                rpc_utils_group_id: 0x03,
+                version: PROTOCOL_VERSION_MAX,
+            }
+        );
+    }
+
+    #[test]
+    fn test_init_assigns_groups_by_name() {
+        // rpc_utils answers first
+        let mut client = mock_client(&[
+            &[
+                0x04, 0x00, 0xFF, 0x01, 0x03, 0x00, 0x00, b'r', b'p', b'c', b'_', b'u', b't', b'i',
+                b'l', b's',
+            ],
+            &[
+                0x04, 0x00, 0xFF, 0x00, 0x02, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+            ],
+        ]);
+        let info = block_on(client.init()).unwrap();
+
+        assert_eq!(info.bt_rpc_group_id, 0x02);
+        assert_eq!(info.rpc_utils_group_id, 0x03);
+    }
+
+    #[test]
+    fn test_init_skips_unrelated_packets() {
+        let mut client = mock_client(&[
+            // Late response from before the reset
+            &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6],
+            &[
+                0x04, 0x00, 0xFF, 0x00, 0x02, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+            ],
+            &[
+                0x04, 0x00, 0xFF, 0x01, 0x03, 0x00, 0x00, b'r', b'p', b'c', b'_', b'u', b't', b'i',
+                b'l', b's',
+            ],
+        ]);
+        let info = block_on(client.init()).unwrap();
+
+        assert_eq!(info.bt_rpc_group_id, 0x02);
+        assert_eq!(info.rpc_utils_group_id, 0x03);
+    }
+
+    #[test]
+    fn test_init_fails_without_both_groups() {
+        let bt_rpc_reply: &[u8] = &[
+            0x04, 0x00, 0xFF, 0x00, 0x02, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+        ];
+        let stray: &[u8] = &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6];
+        let mut packets = [stray; INIT_MAX_PACKETS];
+        packets[0] = bt_rpc_reply;
+        let mut client = mock_client(&packets);
+
+        let result = block_on(client.init());
+        assert!(matches!(result, Err(RpcError::GroupNotRegistered)));
+    }
+
+    #[test]
+    fn test_init_with_retry_resends_after_timeout() {
+        let mut client = mock_client(&[
+            &[
+                0x04, 0x00, 0xFF, 0x00, 0x02, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+            ],
+            &[
+                0x04, 0x00, 0xFF, 0x01, 0x03, 0x00, 0x00, b'r', b'p', b'c', b'_', b'u', b't', b'i',
+                b'l', b's',
+            ],
+        ]);
+        client.transport.stall_reads(1);
+
+        let info = block_on(client.init_with_retry(|| core::future::ready(()))).unwrap();
+        assert_eq!(info.bt_rpc_group_id, 0x02);
+        assert_eq!(info.rpc_utils_group_id, 0x03);
+
+        // Both init packets were sent twice
+        assert_eq!(client.transport.sent_packets().len(), 4);
+        assert_eq!(
+            client.transport.sent_packets()[0],
+            client.transport.sent_packets()[2]
+        );
+    }
+
+    #[test]
+    fn test_init_with_retry_gives_up() {
+        let mut client = mock_client(&[]);
+        client.transport.stall_reads(usize::MAX);
+
+        let result = block_on(client.init_with_retry(|| core::future::ready(())));
+        assert!(matches!(result, Err(RpcError::Timeout)));
+        assert_eq!(
+            client.transport.sent_packets().len(),
+            2 * (INIT_RETRIES + 1)
+        );
+    }
+
+    #[test]
+    fn test_handle_incoming_init_replies_with_group_id() {
+        let mut client = mock_client(&[]);
+        // Init packet as the spec lays it out: Max Version, Min Version, name
+        let init = PacketBuilder::<64>::new().init(0x05, "bt_rpc").unwrap();
+        assert_eq!(
+            init.as_slice(),
+            &[
+                0x04, 0x00, 0xFF, 0x05, 0xFF, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c'
+            ]
+        );
+        block_on(client.handle_incoming_init(init.as_slice())).unwrap();
+
+        let sent = client.transport.sent_packets();
+        let reply = &sent[0];
+        assert_eq!(
+            reply.as_slice(),
+            &[
+                0x04, 0x00, 0xFF, 0x05, 0x00, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c'
+            ]
+        );
+        assert_eq!(
+            parse_init_response(reply).unwrap(),
+            Some(InitResponse {
+                group_name: b"bt_rpc",
+                group_id: LOCAL_BT_RPC_GROUP,
+                version: PROTOCOL_VERSION_MAX,
+            })
+        );
+        assert_eq!(client.bt_rpc_group_id(), 0x05);
+    }
+
+    #[test]
+    fn test_poll_events_answers_incoming_init() {
+        let init = PacketBuilder::<64>::new().init(0x02, "rpc_utils").unwrap();
+        let mut client = mock_client(&[init.as_slice()]);
+        block_on(client.poll_events()).unwrap();
+
+        assert_eq!(client.transport.sent_packets().len(), 1);
+        assert_eq!(client.transport.sent_packets()[0][4], LOCAL_RPC_UTILS_GROUP);
+        assert_eq!(client.rpc_utils_group_id(), 0x02);
+    }
+
+    #[test]
+    fn test_handle_incoming_init_rejects_unknown_group() {
+        let mut client = mock_client(&[]);
+        let init = PacketBuilder::<64>::new().init(0x05, "nfc_rpc").unwrap();
+        let result = block_on(client.handle_incoming_init(init.as_slice()));
+
+        assert!(matches!(result, Err(RpcError::GroupNotRegistered)));
+        assert!(client.transport.sent_packets().is_empty());
+
+        // Not an init packet at all
+        let result = block_on(client.handle_incoming_init(&[0x01, 0x00, 0x00, 0x00, 0x00]));
+        assert!(matches!(result, Err(RpcError::InvalidResponse)));
+    }
+
+    #[test]
+    fn test_handle_incoming_init_reports_remote_version_range() {
+        let mut client = mock_client(&[]);
+        let result = block_on(client.handle_incoming_init(&[
+            0x04, 0x00, 0xFF, 0x05, 0xFF, 0x03, 0x01, b'b', b't', b'_', b'r', b'p', b'c',
+        ]));
+
+        assert!(matches!(
+            result,
+            Err(RpcError::VersionMismatch { min: 1, max: 3 })
+        ));
+        assert!(client.transport.sent_packets().is_empty());
+    }
+
+    #[test]
+    fn test_init_response_negotiates_highest_common_version() {
+        // Remote supports 0..=1, one past the highest version spoken here
+        let response = [0x04, 0x00, 0xFF, 0x00, 0x02, 0x01, 0x00, b'b', b't'];
+        let response = parse_init_response(&response).unwrap().unwrap();
+        assert_eq!(response.group_id, 0x02);
+        assert_eq!(response.version, PROTOCOL_VERSION_MAX);
+    }
+
+    #[test]
+    fn test_init_rejects_unsupported_version() {
+        let mut client = mock_client(&[&[0x04, 0x00, 0xFF, 0x00, 0x02, 0x03, 0x01, b'b', b't']]);
+        let result = block_on(client.init());
+        assert!(matches!(
+            result,
+            Err(RpcError::VersionMismatch { min: 1, max: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_receive_event_sends_ack() {
+        let mut client = mock_client(&[&[0x00, 0x00, 0xFF, 0x01, 0x00, 0xF6]]);
+        let mut buf = [0u8; 32];
+
+        let (header, len) = block_on(client.receive_event(&mut buf)).unwrap();
+        assert_eq!(header.packet_type, PacketType::Event);
+        assert_eq!(len, 6);
+        assert_eq!(
+            client.transport.sent_packets()[0],
+            [0x02, 0x00, 0xFF, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_send_packet_flushes() {
+        let mut client = mock_client(&[]);
+        block_on(client.send_packet(&[0x04, 0x00, 0xFF, 0x00, 0xFF])).unwrap();
+
+        assert_eq!(client.transport.sent_packets().len(), 1);
+        assert_eq!(client.transport.flush_count(), 1);
+    }
+
+    #[test]
+    fn test_rpc_error_source() {
+        use core::error::Error;
+
+        let err = RpcError::Cbor(CborError::BufferTooSmall);
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "CBOR buffer too small");
+        assert!(RpcError::Timeout.source().is_none());
+
+        // Composes with `?` into a boxed error on std targets
+        fn fails() -> Result<(), std::boxed::Box<dyn Error>> {
+            Err(RpcError::InvalidResponse)?
+        }
+        assert_eq!(fails().unwrap_err().to_string(), "Invalid response");
+    }
+
+    #[test]
+    fn test_send_command_raw_returns_payload() {
+        let mut client = mock_client(&[&[0x01, 0x00, 0x00, 0x00, 0x00, 0x82, 0x01, 0x02, 0xF6]]);
+        let mut out = [0u8; 16];
+
+        let payload =
+            block_on(client.send_command_raw(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6], &mut out));
+        assert_eq!(payload.unwrap(), &[0x82, 0x01, 0x02, 0xF6]);
+    }
+
+    #[test]
+    fn test_response_for_other_context_is_dropped() {
+        let mut client = mock_client(&[
+            // Late response to an abandoned command in context 3
+            &[0x01, 0x00, 0x03, 0x00, 0x00, 0x20, 0xF6],
+            &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6],
+        ]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert_eq!(result.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_observer_sees_both_directions() {
+        use std::sync::Mutex;
+
+        struct Recorder(Mutex<Vec<(bool, Vec<u8>)>>);
+
+        impl PacketObserver for Recorder {
+            fn on_write(&self, bytes: &[u8]) {
+                self.0.lock().unwrap().push((true, bytes.to_vec()));
+            }
+
+            fn on_read(&self, bytes: &[u8]) {
+                self.0.lock().unwrap().push((false, bytes.to_vec()));
+            }
+        }
+
+        static RECORDER: Recorder = Recorder(Mutex::new(Vec::new()));
+
+        let response = [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6];
+        let mut client = mock_client(&[&response]).with_observer(&RECORDER);
+        block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6])).unwrap();
+
+        let seen = RECORDER.0.lock().unwrap();
+        assert_eq!(
+            *seen,
+            [
+                (true, std::vec![0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]),
+                (false, response.to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_read_is_no_data() {
+        let mut client = mock_client(&[]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert!(matches!(result, Err(RpcError::NoData)));
+    }
+
+    #[test]
+    fn test_poll_events_acks_and_dispatches() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+        let mut client = mock_client(&[
+            // Disconnected event for connection 3, reason 0x13
+            &[0x00, 0x02, 0xFF, 0x01, 0x00, 0x03, 0x13, 0xF6],
+            &[0x00, 0x07, 0xFF, 0x01, 0x00, 0xF6],
+        ]);
+        client.set_event_handler(|event| {
+            let bit = match event {
+                Event::Disconnected {
+                    conn: ble::ConnHandle(3),
+                    reason: 0x13,
+                } => 1,
+                Event::Unknown { cmd: 0x07, .. } => 2,
+                _ => 4,
+            };
+            SEEN.fetch_or(bit, Ordering::Relaxed);
+        });
+        block_on(client.poll_events()).unwrap();
+
+        assert_eq!(SEEN.load(Ordering::Relaxed), 3);
+        let sent = client.transport.sent_packets();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0], [0x02, 0x02, 0xFF, 0x00, 0x01]);
+        assert_eq!(sent[1], [0x02, 0x07, 0xFF, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_poll_events_returns_when_idle() {
+        let mut client = mock_client(&[]);
+        block_on(client.poll_events()).unwrap();
+        assert!(client.transport.sent_packets().is_empty());
+    }
+
+    #[test]
+    fn test_conn_validation_follows_events() {
+        let mut client = mock_client(&[
+            // Connected: conn 3, err 0
+            &[0x00, 0x01, 0xFF, 0x01, 0x00, 0x03, 0x00, 0xF6],
+            // Connected: conn 4, err 0x3E (failed to establish)
+            &[0x00, 0x01, 0xFF, 0x01, 0x00, 0x04, 0x18, 0x3E, 0xF6],
+        ])
+        .with_conn_validation();
+        assert!(matches!(
+            client.check_conn(ConnHandle(3)),
+            Err(RpcError::InvalidHandle)
+        ));
+
+        block_on(client.poll_events()).unwrap();
+        assert!(client.check_conn(ConnHandle(3)).is_ok());
+        assert!(matches!(
+            client.check_conn(ConnHandle(4)),
+            Err(RpcError::InvalidHandle)
+        ));
+
+        // Disconnected: conn 3, reason 0x13
+        client
+            .transport
+            .push_response(&[0x00, 0x02, 0xFF, 0x01, 0x00, 0x03, 0x13, 0xF6]);
+        block_on(client.poll_events()).unwrap();
+        assert!(matches!(
+            client.check_conn(ConnHandle(3)),
+            Err(RpcError::InvalidHandle)
+        ));
+    }
+
+    #[test]
+    fn test_conn_validation_is_off_by_default() {
+        let client = mock_client(&[]);
+        assert!(client.check_conn(ConnHandle(3)).is_ok());
+    }
+
+    #[test]
+    fn test_oversized_framed_response_reports_needed_len() {
+        let mut frame = std::vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x50];
+        frame.extend([0xAA; 16]);
+        frame.push(0xF6);
+        let prefix = (frame.len() as u16).to_le_bytes();
+
+        let mock = MockTransport::new();
+        mock.push_response(&prefix);
+        mock.push_response(&frame);
+        let transport = FramedTransport::new(mock);
+        let mut client = RpcClient::<_, 16>::new(transport);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert!(matches!(
+            result,
+            Err(RpcError::ResponseTooLarge { needed: 23 })
+        ));
+    }
+
+    #[test]
+    fn test_wait_event_handles_others_first() {
+        let mut client = mock_client(&[
+            // Connected: conn 3, err 0
+            &[0x00, 0x01, 0xFF, 0x01, 0x00, 0x03, 0x00, 0xF6],
+            // Ready: err 0
+            &[0x00, 0x03, 0xFF, 0x01, 0x00, 0x00, 0xF6],
+        ])
+        .with_conn_validation();
+
+        let mut buf = [0u8; 32];
+        let len = block_on(client.wait_event(0x03, &mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[0x00, 0x03, 0xFF, 0x01, 0x00, 0x00, 0xF6]);
+
+        // Both events were acked, and the connection was tracked on the way
+        let sent = client.transport.sent_packets();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0], [0x02, 0x01, 0xFF, 0x00, 0x01]);
+        assert_eq!(sent[1], [0x02, 0x03, 0xFF, 0x00, 0x01]);
+        assert!(client.check_conn(ConnHandle(3)).is_ok());
+    }
+
+    #[test]
+    fn test_command_in_group_addresses_group() {
+        let client = mock_client(&[]);
+        let packet = client
+            .command_in_group::<16>(0x03, 0x07)
+            .cbor_null()
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0x80, 0x07, 0xFF, 0x03, 0x03, 0xF6]);
+    }
+
+    #[test]
+    fn test_transport_error_is_kept() {
+        #[derive(Debug, PartialEq)]
+        enum UartError {
+            Overrun,
+        }
+
+        impl TransportError for UartError {}
+
+        struct FailingTransport;
+
+        impl AsyncTransport for FailingTransport {
+            type Error = UartError;
+
+            async fn write(&mut self, _data: &[u8]) -> Result<usize, Self::Error> {
+                Err(UartError::Overrun)
+            }
+
+            async fn read(&mut self, _buffer: &mut [u8]) -> Result<usize, Self::Error> {
+                Err(UartError::Overrun)
+            }
+        }
+
+        let mut client: RpcClient<_> = RpcClient::new(FailingTransport);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert!(matches!(result, Err(RpcError::Transport)));
+        assert_eq!(client.take_transport_error(), Some(UartError::Overrun));
+        assert_eq!(client.take_transport_error(), None);
+    }
+
+    #[test]
+    fn test_send_event_waits_for_ack() {
+        let mut client = mock_client(&[
+            // Remote event arriving first is acked and handled
+            &[0x00, 0x03, 0xFF, 0x00, 0x00, 0x00],
+            &[0x02, 0x07, 0xFF, 0x00, 0x00],
+        ]);
+
+        let result = block_on(client.send_event(
+            0x00,
+            0x07,
+            |p| p.cbor_uint(1),
+            core::future::pending::<()>(),
+        ));
+        assert!(result.is_ok());
+        assert_eq!(
+            client.transport.sent_packets()[0],
+            [0x00, 0x07, 0xFF, 0x00, 0x00, 0x01]
+        );
+        assert_eq!(
+            client.transport.sent_packets()[1],
+            [0x02, 0x03, 0xFF, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_send_event_times_out_without_ack() {
+        let mut client = mock_client(&[]);
+        client.transport.stall_reads(1);
+
+        let result = block_on(client.send_event(0x00, 0x07, Ok, core::future::ready(())));
+        assert!(matches!(result, Err(RpcError::Timeout)));
+        assert_eq!(client.transport.sent_packets().len(), 1);
+    }
+
+    #[test]
+    fn test_late_responses_release_abandoned_contexts() {
+        let mut client = mock_client(&[]);
+
+        for _ in 0..=64 {
+            client.transport.stall_reads(1);
+            let result = block_on(with_timeout(
+                core::future::ready(()),
+                client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]),
+            ));
+            assert!(matches!(result, Err(RpcError::Timeout)));
+
+            let ctx_id = client.transport.sent_packets().last().unwrap()[0] & 0x7F;
+            client
+                .transport
+                .push_response(&[0x01, 0x00, ctx_id, 0x00, 0x00, 0x00, 0xF6]);
+            block_on(client.poll_events()).unwrap();
+        }
+
+        // A late response arriving while the next command waits is dropped too
+        client.transport.stall_reads(1);
+        let result = block_on(with_timeout(
+            core::future::ready(()),
+            client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]),
+        ));
+        assert!(matches!(result, Err(RpcError::Timeout)));
+        client
+            .transport
+            .push_response(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+        client
+            .transport
+            .push_response(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x05, 0xF6]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00, 0x00, 0xF6]));
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(client.alloc_context_id(), Some(0));
+    }
+
+    #[test]
+    fn test_command_before_init_is_rejected() {
+        let mut client = mock_client(&[]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0xFF, 0xFF, 0xF6]));
+
+        assert!(matches!(result, Err(RpcError::GroupNotRegistered)));
+        assert!(client.transport.sent_packets().is_empty());
+        assert_eq!(client.alloc_context_id(), Some(0));
+    }
+
+    #[test]
+    fn test_command_shorter_than_header_is_rejected() {
+        let mut client = mock_client(&[]);
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF, 0x00]));
+        assert!(matches!(result, Err(RpcError::InvalidResponse)));
+
+        let result = block_on(client.send_command(&mut [0x80, 0x00, 0xFF]));
+        assert!(matches!(result, Err(RpcError::InvalidResponse)));
+        assert!(client.transport.sent_packets().is_empty());
+        assert_eq!(client.alloc_context_id(), Some(0));
+    }
+
+    #[test]
+    fn test_client_is_send_and_sync() {
+        fn assert_send_sync<S: Send + Sync>() {}
+        assert_send_sync::<RpcClient<MockTransport>>();
+        assert_send_sync::<ble::Ble<MockTransport>>();
+    }
 }
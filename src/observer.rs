@@ -0,0 +1,45 @@
+//! Packet tracing hook
+//!
+//! The client can report every packet it writes or reads to an observer,
+//! which makes the wire traffic inspectable without forking the crate. The
+//! observer decides where the bytes go (`log`, `defmt`, RTT, ...), so the
+//! crate itself stays free of logging dependencies.
+//!
+//! # Example
+//!
+//! ```ignore
+//! struct DefmtObserver;
+//!
+//! impl PacketObserver for DefmtObserver {
+//!     fn on_write(&self, bytes: &[u8]) {
+//!         defmt::trace!("tx {=[u8]:02x}", bytes);
+//!     }
+//!
+//!     fn on_read(&self, bytes: &[u8]) {
+//!         defmt::trace!("rx {=[u8]:02x}", bytes);
+//!     }
+//! }
+//!
+//! static OBSERVER: DefmtObserver = DefmtObserver;
+//!
+//! let mut ble = Ble::new(transport).await?.with_observer(&OBSERVER);
+//! ```
+
+/// Receives a copy of every packet the client sends or receives
+///
+/// Both methods default to doing nothing, so an observer only needs to
+/// implement the direction it cares about.
+///
+/// Observers are `'static` references, normally to a `static`, which must be
+/// `Sync` anyway. Requiring it here keeps the client `Send`.
+pub trait PacketObserver: Sync {
+    /// Called with each packet before it is written to the transport
+    fn on_write(&self, bytes: &[u8]) {
+        let _ = bytes;
+    }
+
+    /// Called with each packet read from the transport
+    fn on_read(&self, bytes: &[u8]) {
+        let _ = bytes;
+    }
+}
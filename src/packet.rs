@@ -95,13 +95,20 @@
        63 62 61 72: CBOR text string ("bar")
        f6: CBOR null
 */
-use minicbor::encode::Encoder;
+use minicbor::data::Tag;
+use minicbor::decode::Decoder;
+use minicbor::encode::{Encoder, Write};
+
+use crate::RpcError;
+use crate::version::{PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN};
 
 /// CBOR encoding error
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CborError {
     BufferTooSmall,
     EncodingError,
+    DecodingError,
 }
 
 impl core::fmt::Display for CborError {
@@ -109,18 +116,27 @@ impl core::fmt::Display for CborError {
         match self {
             CborError::BufferTooSmall => write!(f, "CBOR buffer too small"),
             CborError::EncodingError => write!(f, "CBOR encoding error"),
+            CborError::DecodingError => write!(f, "CBOR decoding error"),
         }
     }
 }
 
+impl core::error::Error for CborError {}
+
 impl From<minicbor::encode::Error<CborError>> for CborError {
-    fn from(_: minicbor::encode::Error<CborError>) -> Self {
-        CborError::EncodingError
+    fn from(e: minicbor::encode::Error<CborError>) -> Self {
+        // The only write error `SliceWriter` produces is running out of space
+        if e.is_write() {
+            CborError::BufferTooSmall
+        } else {
+            CborError::EncodingError
+        }
     }
 }
 
 /// Packet type identifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum PacketType {
     Event = 0x00,
@@ -131,145 +147,814 @@ pub enum PacketType {
     Command = 0x80,
 }
 
+impl PacketType {
+    /// Bits of a command type byte that carry the source context ID
+    const CONTEXT_MASK: u8 = 0x7F;
+
+    /// Split a raw type byte into the packet type and source context ID
+    ///
+    /// Only command packets carry a context ID in the type byte; it is 0 for
+    /// every other type.
+    pub fn from_byte(byte: u8) -> Result<(PacketType, u8), CborError> {
+        let packet_type = PacketType::try_from(byte)?;
+        let src_ctx_id = match packet_type {
+            PacketType::Command => byte & Self::CONTEXT_MASK,
+            _ => 0,
+        };
+        Ok((packet_type, src_ctx_id))
+    }
+
+    /// Whether the raw type byte `byte` is a response
+    pub fn is_response(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::Response))
+    }
+
+    /// Whether the raw type byte `byte` is an event
+    pub fn is_event(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::Event))
+    }
+
+    /// Whether the raw type byte `byte` is an event acknowledgment
+    pub fn is_event_ack(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::EventAck))
+    }
+
+    /// Whether the raw type byte `byte` is an error report
+    pub fn is_error_report(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::ErrorReport))
+    }
+
+    /// Whether the raw type byte `byte` is an init packet
+    pub fn is_init(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::Init))
+    }
+
+    /// Whether the raw type byte `byte` is a command, whatever its context ID
+    pub fn is_command(byte: u8) -> bool {
+        matches!(Self::try_from(byte), Ok(PacketType::Command))
+    }
+
+    /// Build the raw type byte, the inverse of `from_byte`
+    ///
+    /// `src_ctx_id` is only encoded for command packets and ignored otherwise.
+    pub fn to_byte(self, src_ctx_id: u8) -> u8 {
+        match self {
+            PacketType::Command => self as u8 | (src_ctx_id & Self::CONTEXT_MASK),
+            _ => self as u8,
+        }
+    }
+}
+
+impl TryFrom<u8> for PacketType {
+    type Error = CborError;
+
+    /// Map a raw type byte to a packet type
+    ///
+    /// Any byte with the command bit set is a command; the low bits carry the
+    /// source context ID and are not part of the type.
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        if byte & 0x80 != 0 {
+            return Ok(PacketType::Command);
+        }
+
+        match byte {
+            0x00 => Ok(PacketType::Event),
+            0x01 => Ok(PacketType::Response),
+            0x02 => Ok(PacketType::EventAck),
+            0x03 => Ok(PacketType::ErrorReport),
+            0x04 => Ok(PacketType::Init),
+            _ => Err(CborError::DecodingError),
+        }
+    }
+}
+
+/// Length of the fixed nRF RPC packet header
+pub const HEADER_LEN: usize = 5;
+
+/// Decoded nRF RPC packet header
+///
+/// For command packets the source context ID is carried in the low bits of the
+/// type byte; for all other packet types `src_ctx_id` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub packet_type: PacketType,
+    pub src_ctx_id: u8,
+    pub command_id: u8,
+    pub dst_ctx_id: u8,
+    pub src_group_id: u8,
+    pub dst_group_id: u8,
+}
+
+impl PacketHeader {
+    /// Parse the 5-byte header at the start of `bytes`
+    pub fn parse(bytes: &[u8]) -> Result<PacketHeader, CborError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(CborError::BufferTooSmall);
+        }
+
+        let (packet_type, src_ctx_id) = PacketType::from_byte(bytes[0])?;
+
+        Ok(PacketHeader {
+            packet_type,
+            src_ctx_id,
+            command_id: bytes[1],
+            dst_ctx_id: bytes[2],
+            src_group_id: bytes[3],
+            dst_group_id: bytes[4],
+        })
+    }
+}
+
 /// Builder for constructing NRF RPC packets
 ///
 /// Note: This is exposed for testing purposes only. Use the `Ble` struct for normal usage.
 #[doc(hidden)]
-pub struct PacketBuilder<const N: usize> {
-    buffer: [u8; N],
-    pos: usize,
+pub type PacketBuilder<const N: usize> = GenericPacketBuilder<storage::ArrayStorage<N>>;
+
+/// Packet builder that writes into a borrowed buffer
+///
+/// Offers the same methods as `PacketBuilder`, but builds directly into a
+/// caller-owned slice (e.g. a DMA-capable static) instead of an owned stack
+/// array, so the finished packet can be handed to the transport without a
+/// copy. Since the buffer size is only known at runtime, header writers
+/// return `CborError::BufferTooSmall` instead of failing to compile.
+///
+/// ```ignore
+/// // `tx_buf` is any buffer the caller owns, e.g. a `&'static mut [u8]`
+/// let packet = PacketWriter::new(tx_buf)
+///     .command(0x00, cmd_id, 0xFF, grp, grp)?
+///     .cbor_bytes(&value)?
+///     .cbor_null()?;
+/// transport.write(packet.as_slice()).await?;
+/// ```
+#[doc(hidden)]
+pub type PacketWriter<'a> = GenericPacketBuilder<storage::SliceStorage<'a>>;
+
+/// Packet builder backed by a `heapless::Vec`
+///
+/// Offers the same methods as `PacketBuilder`, but the packet only occupies as
+/// many bytes as have been written, so `CAP` can be a generous upper bound
+/// rather than a per-command guess. `CborError::BufferTooSmall` is returned
+/// only once the packet would exceed `CAP`.
+///
+/// Requires the `heapless` feature.
+#[cfg(feature = "heapless")]
+#[doc(hidden)]
+pub type HeaplessPacketBuilder<const CAP: usize> = GenericPacketBuilder<heapless::Vec<u8, CAP>>;
+
+/// Packet builder over the storage `S`
+///
+/// `PacketBuilder`, `PacketWriter` and `HeaplessPacketBuilder` are this type
+/// with different storage. Encoding is implemented once here; the storage
+/// only decides where the bytes go and how much room there is.
+#[doc(hidden)]
+pub struct GenericPacketBuilder<S: storage::PacketStorage> {
+    storage: S,
 }
 
-impl<const N: usize> PacketBuilder<N> {
-    pub fn new() -> Self {
-        Self {
-            buffer: [0u8; N],
-            pos: 0,
+impl<S: storage::PacketStorage> GenericPacketBuilder<S> {
+    /// Build an initialization packet
+    ///
+    /// Format: 0x04 | 0x00 | 0xFF | src_grp_id | 0xFF | max version | min version | group_name
+    ///
+    /// The versions advertised are `PROTOCOL_VERSION_MAX` and
+    /// `PROTOCOL_VERSION_MIN`.
+    ///
+    /// Returns `CborError::BufferTooSmall` if the group name does not fit.
+    pub fn init(self, src_group_id: u8, group_name: &str) -> Result<Self, CborError> {
+        self.header([
+            PacketType::Init as u8,
+            0x00, // Command ID unused for init
+            0xFF, // Destination context unknown
+            src_group_id,
+            0xFF, // Destination group unknown
+        ])?
+        .append(&[PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN])?
+        .append(group_name.as_bytes())
+    }
+
+    /// Build the reply to an init packet received from the remote
+    ///
+    /// Format: 0x04 | 0x00 | 0xFF | src_grp_id | dst_grp_id | max version | min version | group_name
+    ///
+    /// `src_group_id` is the remote's ID for the group, taken from its init
+    /// packet, and `dst_group_id` the local one. The versions advertised are
+    /// `PROTOCOL_VERSION_MAX` and `PROTOCOL_VERSION_MIN`.
+    ///
+    /// Returns `CborError::BufferTooSmall` if the group name does not fit.
+    pub fn init_response(
+        self,
+        src_group_id: u8,
+        dst_group_id: u8,
+        group_name: &[u8],
+    ) -> Result<Self, CborError> {
+        self.header([
+            PacketType::Init as u8,
+            0x00, // Command ID unused for init
+            0xFF, // Destination context unknown
+            src_group_id,
+            dst_group_id,
+        ])?
+        .append(&[PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN])?
+        .append(group_name)
+    }
+
+    /// Encode an unsigned integer in CBOR format to the payload
+    pub fn cbor_uint(self, value: u64) -> Result<Self, CborError> {
+        self.encode(|e| e.u64(value).map(drop))
+    }
+
+    /// Encode a signed integer in CBOR format to the payload
+    pub fn cbor_int(self, value: i64) -> Result<Self, CborError> {
+        self.encode(|e| e.i64(value).map(drop))
+    }
+
+    /// Encode bytes in CBOR format to the payload
+    pub fn cbor_bytes(self, bytes: &[u8]) -> Result<Self, CborError> {
+        self.encode(|e| e.bytes(bytes).map(drop))
+    }
+
+    /// Encode a string in CBOR format to the payload
+    pub fn cbor_str(self, s: &str) -> Result<Self, CborError> {
+        self.encode(|e| e.str(s).map(drop))
+    }
+
+    /// Encode a boolean in CBOR format to the payload (0xF5 / 0xF4)
+    pub fn cbor_bool(self, value: bool) -> Result<Self, CborError> {
+        self.encode(|e| e.bool(value).map(drop))
+    }
+
+    /// Encode a single-precision float in CBOR format to the payload
+    pub fn cbor_f32(self, value: f32) -> Result<Self, CborError> {
+        self.encode(|e| e.f32(value).map(drop))
+    }
+
+    /// Encode a double-precision float in CBOR format to the payload
+    pub fn cbor_f64(self, value: f64) -> Result<Self, CborError> {
+        self.encode(|e| e.f64(value).map(drop))
+    }
+
+    /// Encode a definite-length CBOR array header to the payload
+    ///
+    /// The next `len` encoded items are the array elements.
+    pub fn cbor_array(self, len: u64) -> Result<Self, CborError> {
+        self.encode(|e| e.array(len).map(drop))
+    }
+
+    /// Encode `items` as a CBOR array of byte strings
+    ///
+    /// Writes the array header for `items.len()` elements, then each item
+    /// as with `cbor_bytes`.
+    pub fn cbor_array_of_bytes(self, items: &[&[u8]]) -> Result<Self, CborError> {
+        let mut builder = self.cbor_array(items.len() as u64)?;
+        for item in items {
+            builder = builder.cbor_bytes(item)?;
         }
+        Ok(builder)
     }
 
-    /// Build an initialization packet
+    /// Encode an indefinite-length CBOR array header (0x9F) to the payload
     ///
-    /// Format: 0x04 | 0x00 | 0xFF | src_grp_id | 0xFF | 0x00 (version) | group_name
-    pub fn init(mut self, src_group_id: u8, group_name: &str) -> Self {
-        self.buffer[0] = PacketType::Init as u8;
-        self.buffer[1] = 0x00; // Command ID unused for init
-        self.buffer[2] = 0xFF; // Destination context unknown
-        self.buffer[3] = src_group_id;
-        self.buffer[4] = 0xFF; // Destination group unknown
-        self.buffer[5] = 0x00; // Version
-        self.pos = 6;
-
-        // Append group name bytes
-        let name_bytes = group_name.as_bytes();
-        self.buffer[self.pos..self.pos + name_bytes.len()].copy_from_slice(name_bytes);
-        self.pos += name_bytes.len();
+    /// The array must be closed with `cbor_break`.
+    pub fn cbor_array_indef(self) -> Result<Self, CborError> {
+        self.encode(|e| e.begin_array().map(drop))
+    }
 
-        self
+    /// Encode a CBOR break (0xFF) closing an indefinite-length item
+    pub fn cbor_break(self) -> Result<Self, CborError> {
+        self.encode(|e| e.end().map(drop))
+    }
+
+    /// Encode a CBOR tag header to the payload
+    ///
+    /// The next encoded item is the tagged value, e.g. tag 2 followed by
+    /// `cbor_bytes` for an unsigned bignum.
+    pub fn cbor_tag(self, tag: u64) -> Result<Self, CborError> {
+        self.encode(|e| e.tag(Tag::new(tag)).map(drop))
+    }
+
+    /// Append already-encoded CBOR bytes to the payload verbatim
+    ///
+    /// The bytes are not validated, so they must form complete CBOR items.
+    pub fn cbor_raw(self, bytes: &[u8]) -> Result<Self, CborError> {
+        self.append(bytes)
+    }
+
+    /// Encode CBOR null (0xF6) - used as packet terminator
+    pub fn cbor_null(self) -> Result<Self, CborError> {
+        self.encode(|e| e.null().map(drop))
+    }
+
+    /// Get the packet bytes as a slice
+    pub fn as_slice(&self) -> &[u8] {
+        self.storage.bytes()
+    }
+
+    /// Get the packet bytes as a mutable slice
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.storage.bytes_mut()
     }
 
+    /// Get the length of the packet
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Check whether nothing has been written to the packet yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clear the packet so the builder can be reused
+    ///
+    /// Only the write position is reset; the buffer is not re-zeroed. The
+    /// chained builder methods take `self` by value, so reuse a builder by
+    /// reassigning the result of the chain to the same binding:
+    ///
+    /// ```ignore
+    /// let mut builder = PacketBuilder::<64>::new();
+    /// loop {
+    ///     builder.reset();
+    ///     builder = builder.command(0x00, cmd_id, 0xFF, grp, grp).cbor_null()?;
+    ///     transport.write(builder.as_slice()).await?;
+    /// }
+    /// ```
+    pub fn reset(&mut self) {
+        self.storage.clear();
+    }
+
+    /// Number of bytes still available in the buffer
+    ///
+    /// Useful to check whether a large `cbor_bytes` call will fit before
+    /// attempting it.
+    pub fn remaining(&self) -> usize {
+        self.storage.capacity() - self.len()
+    }
+
+    /// Start the packet over with `header`
+    fn header(mut self, header: [u8; HEADER_LEN]) -> Result<Self, CborError> {
+        self.storage.clear();
+        self.append(&header)
+    }
+
+    /// Append `bytes` after everything written so far
+    fn append(mut self, bytes: &[u8]) -> Result<Self, CborError> {
+        self.storage.writer().write_all(bytes)?;
+        Ok(self)
+    }
+
+    fn encode(
+        mut self,
+        f: impl FnOnce(&mut Encoder<S::Writer<'_>>) -> Result<(), minicbor::encode::Error<CborError>>,
+    ) -> Result<Self, CborError> {
+        f(&mut Encoder::new(self.storage.writer()))?;
+        Ok(self)
+    }
+}
+
+/// Header writers for storage whose size is only known at runtime
+impl<S: storage::RuntimeCapacity> GenericPacketBuilder<S> {
     /// Build a command packet header
     ///
     /// Format: 0x80 | src_ctx_id | cmd_id | dst_ctx_id | src_grp_id | dst_grp_id
     pub fn command(
-        mut self,
+        self,
+        src_ctx_id: u8,
+        cmd_id: u8,
+        dst_ctx_id: u8,
+        src_grp_id: u8,
+        dst_grp_id: u8,
+    ) -> Result<Self, CborError> {
+        self.header(command_header(
+            src_ctx_id, cmd_id, dst_ctx_id, src_grp_id, dst_grp_id,
+        ))
+    }
+
+    /// Build an event packet
+    ///
+    /// Format: 0x00 | evt_id | 0xFF | src_grp_id | dst_grp_id
+    pub fn event(self, evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> Result<Self, CborError> {
+        self.header(event_header(evt_id, src_grp_id, dst_grp_id))
+    }
+
+    /// Build an event acknowledgment packet
+    ///
+    /// Format: 0x02 | evt_id | 0xFF | src_grp_id | dst_grp_id
+    pub fn event_ack(self, evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> Result<Self, CborError> {
+        self.header(event_ack_header(evt_id, src_grp_id, dst_grp_id))
+    }
+}
+
+impl<const N: usize> PacketBuilder<N> {
+    pub fn new() -> Self {
+        Self {
+            storage: storage::ArrayStorage {
+                buffer: [0u8; N],
+                pos: 0,
+            },
+        }
+    }
+
+    /// Evaluated by header writers to reject `N < HEADER_LEN` at compile time
+    const HEADER_FITS: () = assert!(N >= HEADER_LEN, "buffer too small for a packet header");
+
+    /// Build a command packet header
+    ///
+    /// Format: 0x80 | src_ctx_id | cmd_id | dst_ctx_id | src_grp_id | dst_grp_id
+    ///
+    /// `N` must be at least `HEADER_LEN`; smaller builders fail to compile:
+    ///
+    /// ```compile_fail
+    /// use nrf_rpc::packet::PacketBuilder;
+    ///
+    /// let packet = PacketBuilder::<4>::new().command(0x00, 0x00, 0xFF, 0x00, 0x00);
+    /// ```
+    pub fn command(
+        self,
         src_ctx_id: u8,
         cmd_id: u8,
         dst_ctx_id: u8,
         src_grp_id: u8,
         dst_grp_id: u8,
     ) -> Self {
-        self.buffer[0] = PacketType::Command as u8 | src_ctx_id;
-        self.buffer[1] = cmd_id;
-        self.buffer[2] = dst_ctx_id;
-        self.buffer[3] = src_grp_id;
-        self.buffer[4] = dst_grp_id;
-        self.pos = 5;
-        self
+        self.fixed_header(command_header(
+            src_ctx_id, cmd_id, dst_ctx_id, src_grp_id, dst_grp_id,
+        ))
     }
 
-    /// Encode an unsigned integer in CBOR format to the payload
-    pub fn cbor_uint(mut self, value: u64) -> Result<Self, CborError> {
-        let mut writer = SliceWriter::new(&mut self.buffer[self.pos..]);
-        let mut encoder = Encoder::new(&mut writer);
-        encoder.u64(value)?;
-        self.pos += writer.pos();
-        Ok(self)
+    /// Build an event packet
+    ///
+    /// Format: 0x00 | evt_id | 0xFF | src_grp_id | dst_grp_id
+    ///
+    /// Like `command`, requires `N >= HEADER_LEN` at compile time.
+    pub fn event(self, evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> Self {
+        self.fixed_header(event_header(evt_id, src_grp_id, dst_grp_id))
     }
 
-    /// Encode a signed integer in CBOR format to the payload
-    pub fn cbor_int(mut self, value: i64) -> Result<Self, CborError> {
-        let mut writer = SliceWriter::new(&mut self.buffer[self.pos..]);
-        let mut encoder = Encoder::new(&mut writer);
-        encoder.i64(value)?;
-        self.pos += writer.pos();
-        Ok(self)
+    /// Build an event acknowledgment packet
+    ///
+    /// Format: 0x02 | evt_id | 0xFF | src_grp_id | dst_grp_id
+    ///
+    /// Like `command`, requires `N >= HEADER_LEN` at compile time.
+    pub fn event_ack(self, evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> Self {
+        self.fixed_header(event_ack_header(evt_id, src_grp_id, dst_grp_id))
     }
 
-    /// Encode bytes in CBOR format to the payload
-    pub fn cbor_bytes(mut self, bytes: &[u8]) -> Result<Self, CborError> {
-        let mut writer = SliceWriter::new(&mut self.buffer[self.pos..]);
-        let mut encoder = Encoder::new(&mut writer);
-        encoder.bytes(bytes)?;
-        self.pos += writer.pos();
-        Ok(self)
+    /// Start the packet over with `header`, which always fits
+    fn fixed_header(mut self, header: [u8; HEADER_LEN]) -> Self {
+        let () = Self::HEADER_FITS;
+        self.storage.buffer[..HEADER_LEN].copy_from_slice(&header);
+        self.storage.pos = HEADER_LEN;
+        self
     }
+}
 
-    /// Encode a string in CBOR format to the payload
-    pub fn cbor_str(mut self, s: &str) -> Result<Self, CborError> {
-        let mut writer = SliceWriter::new(&mut self.buffer[self.pos..]);
-        let mut encoder = Encoder::new(&mut writer);
-        encoder.str(s)?;
-        self.pos += writer.pos();
-        Ok(self)
+impl<const N: usize> Default for PacketBuilder<N> {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    /// Encode CBOR null (0xF6) - used as packet terminator
-    pub fn cbor_null(mut self) -> Result<Self, CborError> {
-        let mut writer = SliceWriter::new(&mut self.buffer[self.pos..]);
-        let mut encoder = Encoder::new(&mut writer);
-        encoder.null()?;
-        self.pos += writer.pos();
-        Ok(self)
+impl<'a> PacketWriter<'a> {
+    pub fn new(slice: &'a mut [u8]) -> Self {
+        Self {
+            storage: storage::SliceStorage { slice, pos: 0 },
+        }
     }
+}
 
-    /// Get the packet bytes as a slice
-    pub fn as_slice(&self) -> &[u8] {
-        &self.buffer[..self.pos]
+#[cfg(feature = "heapless")]
+impl<const CAP: usize> HeaplessPacketBuilder<CAP> {
+    pub fn new() -> Self {
+        Self {
+            storage: heapless::Vec::new(),
+        }
     }
 
-    /// Get the length of the packet
-    pub fn len(&self) -> usize {
-        self.pos
+    /// Consume the builder, returning the packet bytes
+    pub fn into_vec(self) -> heapless::Vec<u8, CAP> {
+        self.storage
     }
 }
 
-/// A writer that writes to a mutable slice and tracks position
-struct SliceWriter<'a> {
-    slice: &'a mut [u8],
-    pos: usize,
+#[cfg(feature = "heapless")]
+impl<const CAP: usize> Default for HeaplessPacketBuilder<CAP> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<'a> SliceWriter<'a> {
-    fn new(slice: &'a mut [u8]) -> Self {
-        Self { slice, pos: 0 }
+fn command_header(
+    src_ctx_id: u8,
+    cmd_id: u8,
+    dst_ctx_id: u8,
+    src_grp_id: u8,
+    dst_grp_id: u8,
+) -> [u8; HEADER_LEN] {
+    [
+        PacketType::Command.to_byte(src_ctx_id),
+        cmd_id,
+        dst_ctx_id,
+        src_grp_id,
+        dst_grp_id,
+    ]
+}
+
+fn event_header(evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> [u8; HEADER_LEN] {
+    [
+        PacketType::Event as u8,
+        evt_id,
+        0xFF, // Events are not addressed to a context
+        src_grp_id,
+        dst_grp_id,
+    ]
+}
+
+fn event_ack_header(evt_id: u8, src_grp_id: u8, dst_grp_id: u8) -> [u8; HEADER_LEN] {
+    [
+        PacketType::EventAck as u8,
+        evt_id,
+        0xFF, // Destination context unknown
+        src_grp_id,
+        dst_grp_id,
+    ]
+}
+
+/// Where the packet builders keep their bytes
+///
+/// The traits are public so they can bound `GenericPacketBuilder`, but live
+/// in a private module so no other storage can be plugged in.
+mod storage {
+    use super::CborError;
+
+    /// Bytes written so far plus room for more
+    pub trait PacketStorage {
+        type Writer<'w>: minicbor::encode::Write<Error = CborError>
+        where
+            Self: 'w;
+
+        /// Writer appending after the bytes already written
+        fn writer(&mut self) -> Self::Writer<'_>;
+
+        /// The bytes written so far
+        fn bytes(&self) -> &[u8];
+
+        /// Mutable view of `bytes`
+        fn bytes_mut(&mut self) -> &mut [u8];
+
+        /// Forget everything written so far
+        fn clear(&mut self);
+
+        /// Total number of bytes the storage can hold
+        fn capacity(&self) -> usize;
+    }
+
+    /// Storage whose size is not checked at compile time, so header writers
+    /// have to be fallible
+    pub trait RuntimeCapacity: PacketStorage {}
+
+    /// Fixed-size array owned by the builder
+    pub struct ArrayStorage<const N: usize> {
+        pub(super) buffer: [u8; N],
+        pub(super) pos: usize,
+    }
+
+    impl<const N: usize> PacketStorage for ArrayStorage<N> {
+        type Writer<'w> = SliceWriter<'w>;
+
+        fn writer(&mut self) -> SliceWriter<'_> {
+            SliceWriter::new(&mut self.buffer, &mut self.pos)
+        }
+
+        fn bytes(&self) -> &[u8] {
+            &self.buffer[..self.pos]
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.buffer[..self.pos]
+        }
+
+        fn clear(&mut self) {
+            self.pos = 0;
+        }
+
+        fn capacity(&self) -> usize {
+            N
+        }
+    }
+
+    /// Caller-owned buffer borrowed by the builder
+    pub struct SliceStorage<'a> {
+        pub(super) slice: &'a mut [u8],
+        pub(super) pos: usize,
     }
 
-    fn pos(&self) -> usize {
-        self.pos
+    impl PacketStorage for SliceStorage<'_> {
+        type Writer<'w>
+            = SliceWriter<'w>
+        where
+            Self: 'w;
+
+        fn writer(&mut self) -> SliceWriter<'_> {
+            SliceWriter::new(self.slice, &mut self.pos)
+        }
+
+        fn bytes(&self) -> &[u8] {
+            &self.slice[..self.pos]
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.slice[..self.pos]
+        }
+
+        fn clear(&mut self) {
+            self.pos = 0;
+        }
+
+        fn capacity(&self) -> usize {
+            self.slice.len()
+        }
+    }
+
+    impl RuntimeCapacity for SliceStorage<'_> {}
+
+    #[cfg(feature = "heapless")]
+    impl<const CAP: usize> PacketStorage for heapless::Vec<u8, CAP> {
+        type Writer<'w> = VecWriter<'w, CAP>;
+
+        fn writer(&mut self) -> VecWriter<'_, CAP> {
+            VecWriter(self)
+        }
+
+        fn bytes(&self) -> &[u8] {
+            self
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            self
+        }
+
+        fn clear(&mut self) {
+            heapless::Vec::clear(self);
+        }
+
+        fn capacity(&self) -> usize {
+            CAP
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    impl<const CAP: usize> RuntimeCapacity for heapless::Vec<u8, CAP> {}
+
+    /// Writer that appends to a slice at a position owned by the builder
+    pub struct SliceWriter<'a> {
+        slice: &'a mut [u8],
+        pos: &'a mut usize,
+    }
+
+    impl<'a> SliceWriter<'a> {
+        pub fn new(slice: &'a mut [u8], pos: &'a mut usize) -> Self {
+            Self { slice, pos }
+        }
+    }
+
+    impl minicbor::encode::Write for SliceWriter<'_> {
+        type Error = CborError;
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+            // Slicing the remainder first avoids overflowing `pos + buf.len()`
+            let dst = self
+                .slice
+                .get_mut(*self.pos..)
+                .and_then(|rest| rest.get_mut(..buf.len()))
+                .ok_or(CborError::BufferTooSmall)?;
+            dst.copy_from_slice(buf);
+            *self.pos += buf.len();
+            Ok(())
+        }
+    }
+
+    /// Writer that appends CBOR to a `heapless::Vec` until it is full
+    #[cfg(feature = "heapless")]
+    pub struct VecWriter<'a, const CAP: usize>(pub &'a mut heapless::Vec<u8, CAP>);
+
+    #[cfg(feature = "heapless")]
+    impl<const CAP: usize> minicbor::encode::Write for VecWriter<'_, CAP> {
+        type Error = CborError;
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+            self.0
+                .extend_from_slice(buf)
+                .map_err(|_| CborError::BufferTooSmall)
+        }
     }
 }
 
-impl<'a> minicbor::encode::Write for SliceWriter<'a> {
-    type Error = CborError;
+/// Decoder for reading CBOR values out of a response payload
+///
+/// Mirrors the `cbor_*` encoders on `PacketBuilder`. Any decoding failure is
+/// reported as `RpcError::InvalidResponse`.
+#[doc(hidden)]
+pub struct PacketDecoder<'a> {
+    decoder: Decoder<'a>,
+}
 
-    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
-        if self.pos + buf.len() > self.slice.len() {
-            return Err(CborError::BufferTooSmall);
+impl<'a> PacketDecoder<'a> {
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self {
+            decoder: Decoder::new(payload),
         }
-        self.slice[self.pos..self.pos + buf.len()].copy_from_slice(buf);
-        self.pos += buf.len();
-        Ok(())
+    }
+
+    /// Decode an unsigned integer
+    pub fn decode_u64(&mut self) -> Result<u64, RpcError> {
+        self.decoder.u64().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode an unsigned integer that must fit in a `u8`
+    pub fn decode_u8(&mut self) -> Result<u8, RpcError> {
+        self.decoder.u8().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode an unsigned integer that must fit in a `u16`
+    pub fn decode_u16(&mut self) -> Result<u16, RpcError> {
+        self.decoder.u16().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode an unsigned integer that must fit in a `u32`
+    pub fn decode_u32(&mut self) -> Result<u32, RpcError> {
+        self.decoder.u32().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a signed integer
+    pub fn decode_i64(&mut self) -> Result<i64, RpcError> {
+        self.decoder.i64().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a signed integer that must fit in an `i32`, e.g. an error code
+    ///
+    /// Returns `RpcError::InvalidResponse` for values outside the `i32` range.
+    pub fn decode_i32(&mut self) -> Result<i32, RpcError> {
+        i32::try_from(self.decode_i64()?).map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a signed integer that must fit in an `i8`, e.g. an RSSI
+    ///
+    /// Returns `RpcError::InvalidResponse` for values outside the `i8` range.
+    pub fn decode_i8(&mut self) -> Result<i8, RpcError> {
+        self.decoder.i8().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a boolean
+    pub fn decode_bool(&mut self) -> Result<bool, RpcError> {
+        self.decoder.bool().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a byte string, copying it into `out`
+    ///
+    /// Returns `RpcError::InvalidResponse` if the byte string does not fit.
+    pub fn decode_bytes<'b>(&mut self, out: &'b mut [u8]) -> Result<&'b [u8], RpcError> {
+        let bytes = self
+            .decoder
+            .bytes()
+            .map_err(|_| RpcError::InvalidResponse)?;
+        let out = out
+            .get_mut(..bytes.len())
+            .ok_or(RpcError::InvalidResponse)?;
+        out.copy_from_slice(bytes);
+        Ok(out)
+    }
+
+    /// Decode a byte string, borrowing it from the payload
+    pub fn decode_bytes_borrowed(&mut self) -> Result<&'a [u8], RpcError> {
+        self.decoder.bytes().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a byte string that must be exactly `L` bytes long
+    pub fn decode_byte_array<const L: usize>(&mut self) -> Result<[u8; L], RpcError> {
+        let bytes = self
+            .decoder
+            .bytes()
+            .map_err(|_| RpcError::InvalidResponse)?;
+        bytes.try_into().map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Decode a text string, copying it into `out`
+    ///
+    /// Returns `RpcError::InvalidResponse` if the string does not fit.
+    pub fn decode_str<'b>(&mut self, out: &'b mut [u8]) -> Result<&'b str, RpcError> {
+        let s = self.decoder.str().map_err(|_| RpcError::InvalidResponse)?;
+        let out = out.get_mut(..s.len()).ok_or(RpcError::InvalidResponse)?;
+        out.copy_from_slice(s.as_bytes());
+        core::str::from_utf8(out).map_err(|_| RpcError::InvalidResponse)
+    }
+
+    /// Consume the null that terminates every response
+    ///
+    /// Call after the last field to check that the response holds no more
+    /// values than expected.
+    pub fn expect_null(&mut self) -> Result<(), RpcError> {
+        self.decoder.null().map_err(|_| RpcError::InvalidResponse)
     }
 }
 
@@ -292,15 +977,165 @@ mod tests {
         assert_eq!(packet3.as_slice(), &[0x03]);
     }
 
+    #[test]
+    fn test_cbor_bool_encoding() {
+        let packet = PacketBuilder::<8>::new()
+            .cbor_bool(true)
+            .unwrap()
+            .cbor_bool(false)
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0xF5, 0xF4]);
+    }
+
+    #[test]
+    fn test_cbor_array_encoding() {
+        let packet = PacketBuilder::<8>::new()
+            .cbor_array(2)
+            .unwrap()
+            .cbor_uint(1)
+            .unwrap()
+            .cbor_uint(2)
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0x82, 0x01, 0x02]);
+
+        // Lengths above 23 need a separate length byte
+        let packet = PacketBuilder::<8>::new().cbor_array(24).unwrap();
+        assert_eq!(packet.as_slice(), &[0x98, 0x18]);
+    }
+
+    #[test]
+    fn test_cbor_array_of_bytes_encoding() {
+        let packet = PacketBuilder::<16>::new()
+            .cbor_array_of_bytes(&[&[0xAA, 0xBB], &[]])
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0x82, 0x42, 0xAA, 0xBB, 0x40]);
+
+        // An item that does not fit fails instead of truncating the array
+        assert!(matches!(
+            PacketBuilder::<4>::new().cbor_array_of_bytes(&[&[0x01, 0x02, 0x03]]),
+            Err(CborError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_cbor_array_indef_encoding() {
+        let packet = PacketBuilder::<8>::new()
+            .cbor_array_indef()
+            .unwrap()
+            .cbor_uint(1)
+            .unwrap()
+            .cbor_uint(2)
+            .unwrap()
+            .cbor_break()
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0x9F, 0x01, 0x02, 0xFF]);
+    }
+
+    #[test]
+    fn test_cbor_float_encoding() {
+        let packet = PacketBuilder::<8>::new().cbor_f32(1.0).unwrap();
+        assert_eq!(packet.as_slice(), &[0xFA, 0x3F, 0x80, 0x00, 0x00]);
+
+        let packet = PacketBuilder::<16>::new().cbor_f64(-2.5).unwrap();
+        assert_eq!(
+            packet.as_slice(),
+            &[0xFB, 0xC0, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
     #[test]
     fn test_init_packet() {
         // Build init packet for "bt_rpc"
-        let packet = PacketBuilder::<64>::new().init(0x00, "bt_rpc");
+        let packet = PacketBuilder::<64>::new().init(0x00, "bt_rpc").unwrap();
+
+        let expected = &[
+            0x04, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+        ];
+        assert_eq!(packet.as_slice(), expected);
+        assert_eq!(packet.as_slice()[5], PROTOCOL_VERSION_MAX);
+        assert_eq!(packet.as_slice()[6], PROTOCOL_VERSION_MIN);
+    }
+
+    #[test]
+    fn test_empty_builder() {
+        let packet = PacketBuilder::<16>::new();
+        assert!(packet.is_empty());
+        assert_eq!(packet.len(), 0);
+        assert_eq!(packet.remaining(), 16);
+    }
+
+    #[test]
+    fn test_near_full_builder() {
+        let packet = PacketBuilder::<8>::new()
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .cbor_uint(28)
+            .unwrap();
+        assert!(!packet.is_empty());
+        assert_eq!(packet.remaining(), 1);
+
+        // One byte left: a null fits but a two-byte uint does not
+        assert_eq!(packet.cbor_uint(28).err(), Some(CborError::BufferTooSmall));
+        let packet = PacketBuilder::<8>::new()
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .cbor_uint(28)
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+        assert_eq!(packet.remaining(), 0);
+    }
+
+    #[test]
+    fn test_reset_and_rebuild() {
+        let mut builder = PacketBuilder::<32>::new()
+            .command(0x00, 0x04, 0xFF, 0x00, 0x00)
+            .cbor_str("first packet")
+            .unwrap();
+
+        builder.reset();
+        assert!(builder.is_empty());
+        assert_eq!(builder.remaining(), 32);
+
+        builder = builder
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .cbor_uint(28)
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+        let fresh = PacketBuilder::<32>::new()
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .cbor_uint(28)
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+        assert_eq!(builder.as_slice(), fresh.as_slice());
+    }
+
+    #[test]
+    fn test_init_response_packet() {
+        let packet = PacketBuilder::<64>::new()
+            .init_response(0x05, 0x00, b"bt_rpc")
+            .unwrap();
 
         let expected = &[
-            0x04, 0x00, 0xFF, 0x00, 0xFF, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
+            0x04, 0x00, 0xFF, 0x05, 0x00, 0x00, 0x00, b'b', b't', b'_', b'r', b'p', b'c',
         ];
         assert_eq!(packet.as_slice(), expected);
+        assert_eq!(
+            PacketBuilder::<12>::new()
+                .init_response(0x05, 0x00, b"bt_rpc")
+                .err(),
+            Some(CborError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_init_packet_too_long() {
+        // 5 header bytes, 2 version bytes + "bt_rpc" needs 13 bytes
+        assert_eq!(
+            PacketBuilder::<12>::new().init(0x00, "bt_rpc").err(),
+            Some(CborError::BufferTooSmall)
+        );
+        assert!(PacketBuilder::<13>::new().init(0x00, "bt_rpc").is_ok());
     }
 
     #[test]
@@ -322,4 +1157,355 @@ mod tests {
         ]; // null
         assert_eq!(packet.as_slice(), expected);
     }
+
+    #[test]
+    fn test_event_ack_packet() {
+        let packet = PacketBuilder::<5>::new().event_ack(0x07, 0x00, 0x01);
+        assert_eq!(packet.as_slice(), &[0x02, 0x07, 0xFF, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_packet_type_from_byte() {
+        assert_eq!(PacketType::try_from(0x85), Ok(PacketType::Command));
+        assert_eq!(PacketType::try_from(0x80), Ok(PacketType::Command));
+        assert_eq!(PacketType::try_from(0x04), Ok(PacketType::Init));
+        assert_eq!(PacketType::try_from(0x00), Ok(PacketType::Event));
+        assert_eq!(PacketType::try_from(0x7F), Err(CborError::DecodingError));
+    }
+
+    #[test]
+    fn test_packet_type_split_context_id() {
+        assert_eq!(PacketType::from_byte(0x80), Ok((PacketType::Command, 0)));
+        assert_eq!(PacketType::from_byte(0x83), Ok((PacketType::Command, 3)));
+        assert_eq!(PacketType::from_byte(0x01), Ok((PacketType::Response, 0)));
+        assert_eq!(PacketType::from_byte(0x05), Err(CborError::DecodingError));
+
+        for byte in [0x80, 0x83, 0xFF, 0x00, 0x01, 0x04] {
+            let (packet_type, ctx_id) = PacketType::from_byte(byte).unwrap();
+            assert_eq!(packet_type.to_byte(ctx_id), byte);
+        }
+    }
+
+    #[test]
+    fn test_parse_header_packet_types() {
+        let cases = [
+            (0x00, PacketType::Event),
+            (0x01, PacketType::Response),
+            (0x02, PacketType::EventAck),
+            (0x03, PacketType::ErrorReport),
+            (0x04, PacketType::Init),
+            (0x80, PacketType::Command),
+        ];
+
+        for (byte, packet_type) in cases {
+            let header = PacketHeader::parse(&[byte, 0x07, 0x01, 0x02, 0x03]).unwrap();
+            assert_eq!(header.packet_type, packet_type);
+            assert_eq!(header.src_ctx_id, 0);
+            assert_eq!(header.command_id, 0x07);
+            assert_eq!(header.dst_ctx_id, 0x01);
+            assert_eq!(header.src_group_id, 0x02);
+            assert_eq!(header.dst_group_id, 0x03);
+        }
+    }
+
+    #[test]
+    fn test_parse_header_command_context_id() {
+        // Command type is ORed with the source context ID
+        let header = PacketHeader::parse(&[0x85, 0x04, 0xFF, 0x00, 0x00]).unwrap();
+        assert_eq!(header.packet_type, PacketType::Command);
+        assert_eq!(header.src_ctx_id, 0x05);
+        assert_eq!(header.command_id, 0x04);
+    }
+
+    #[test]
+    fn test_parse_header_errors() {
+        assert_eq!(
+            PacketHeader::parse(&[0x01, 0x00, 0xFF, 0x00]),
+            Err(CborError::BufferTooSmall)
+        );
+        assert_eq!(
+            PacketHeader::parse(&[0x05, 0x00, 0xFF, 0x00, 0x00]),
+            Err(CborError::DecodingError)
+        );
+    }
+
+    #[test]
+    fn test_packet_decoder() {
+        // uint(160), int(-1), true, bytes([0x01, 0x02]), null
+        let payload = [0x18, 0xA0, 0x20, 0xF5, 0x42, 0x01, 0x02, 0xF6];
+        let mut decoder = PacketDecoder::new(&payload);
+
+        assert_eq!(decoder.decode_u64().unwrap(), 160);
+        assert_eq!(decoder.decode_i64().unwrap(), -1);
+        assert!(decoder.decode_bool().unwrap());
+
+        let mut out = [0u8; 8];
+        assert_eq!(decoder.decode_bytes(&mut out).unwrap(), &[0x01, 0x02]);
+
+        // Null is not an integer
+        assert!(matches!(
+            decoder.decode_u64(),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_packet_decoder_i32_range() {
+        // -2^31 fits, 2^31 does not
+        let mut decoder = PacketDecoder::new(&[0x3A, 0x7F, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(decoder.decode_i32().unwrap(), i32::MIN);
+
+        let mut decoder = PacketDecoder::new(&[0x1A, 0x80, 0x00, 0x00, 0x00]);
+        assert!(matches!(
+            decoder.decode_i32(),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_packet_decoder_fixed_width() {
+        // uint(300), bytes([0x01, 0x02]), bytes([0x03])
+        let payload = [0x19, 0x01, 0x2C, 0x42, 0x01, 0x02, 0x41, 0x03];
+
+        let mut decoder = PacketDecoder::new(&payload);
+        assert_eq!(decoder.decode_u16().unwrap(), 300);
+        assert_eq!(decoder.decode_byte_array::<2>().unwrap(), [0x01, 0x02]);
+        assert!(matches!(
+            decoder.decode_byte_array::<2>(),
+            Err(RpcError::InvalidResponse)
+        ));
+
+        // 300 does not fit in a u8
+        let mut decoder = PacketDecoder::new(&payload);
+        assert!(matches!(
+            decoder.decode_u8(),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_packet_decoder_bytes_too_long() {
+        let payload = [0x43, 0x01, 0x02, 0x03];
+        let mut decoder = PacketDecoder::new(&payload);
+
+        let mut out = [0u8; 2];
+        assert!(matches!(
+            decoder.decode_bytes(&mut out),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_packet_decoder_multiple_fields() {
+        // int(-22), "v1.2", null
+        let payload = [0x35, 0x64, b'v', b'1', b'.', b'2', 0xF6];
+        let mut decoder = PacketDecoder::new(&payload);
+
+        assert_eq!(decoder.decode_i64().unwrap(), -22);
+        let mut out = [0u8; 8];
+        assert_eq!(decoder.decode_str(&mut out).unwrap(), "v1.2");
+        decoder.expect_null().unwrap();
+
+        // A response with an extra field fails the terminator check
+        let mut decoder = PacketDecoder::new(&[0x00, 0x01, 0xF6]);
+        decoder.decode_i64().unwrap();
+        assert!(matches!(
+            decoder.expect_null(),
+            Err(RpcError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_packet_writer_matches_builder() {
+        let expected = PacketBuilder::<32>::new()
+            .command(0x01, 0x02, 0xFF, 0x00, 0x00)
+            .cbor_uint(160)
+            .unwrap()
+            .cbor_bytes(&[0xAA, 0xBB])
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+
+        let mut buf = [0u8; 32];
+        let packet = PacketWriter::new(&mut buf)
+            .command(0x01, 0x02, 0xFF, 0x00, 0x00)
+            .unwrap()
+            .cbor_uint(160)
+            .unwrap()
+            .cbor_bytes(&[0xAA, 0xBB])
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+
+        assert_eq!(packet.as_slice(), expected.as_slice());
+        assert_eq!(packet.remaining(), 32 - expected.len());
+    }
+
+    #[test]
+    fn test_packet_writer_buffer_too_small() {
+        let mut buf = [0u8; 4];
+        assert!(matches!(
+            PacketWriter::new(&mut buf).command(0x00, 0x00, 0xFF, 0x00, 0x00),
+            Err(CborError::BufferTooSmall)
+        ));
+
+        let mut buf = [0u8; 6];
+        let packet = PacketWriter::new(&mut buf)
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .unwrap();
+        assert!(matches!(
+            packet.cbor_bytes(&[0x01]),
+            Err(CborError::BufferTooSmall)
+        ));
+
+        let mut buf = [0u8; 8];
+        assert!(matches!(
+            PacketWriter::new(&mut buf).init(0x00, "bt_rpc"),
+            Err(CborError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_slice_writer_tiny_slice() {
+        use minicbor::encode::Write;
+
+        let mut buf = [0u8; 2];
+        let mut pos = 0;
+        let mut writer = storage::SliceWriter::new(&mut buf, &mut pos);
+        assert_eq!(writer.write_all(&[1, 2, 3]), Err(CborError::BufferTooSmall));
+
+        // A failed write leaves the position untouched
+        writer.write_all(&[1, 2]).unwrap();
+        assert_eq!(writer.write_all(&[3]), Err(CborError::BufferTooSmall));
+        assert_eq!(pos, 2);
+
+        let mut empty: [u8; 0] = [];
+        let mut pos = 0;
+        let mut writer = storage::SliceWriter::new(&mut empty, &mut pos);
+        assert_eq!(writer.write_all(&[1]), Err(CborError::BufferTooSmall));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_heapless_builder_matches_builder() {
+        let fixed = PacketBuilder::<64>::new()
+            .command(0x02, 0x0D, 0xFF, 0x00, 0x00)
+            .cbor_uint(3)
+            .unwrap()
+            .cbor_bytes(&[0xAA, 0xBB])
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+        let heapless = HeaplessPacketBuilder::<64>::new()
+            .command(0x02, 0x0D, 0xFF, 0x00, 0x00)
+            .unwrap()
+            .cbor_uint(3)
+            .unwrap()
+            .cbor_bytes(&[0xAA, 0xBB])
+            .unwrap()
+            .cbor_null()
+            .unwrap();
+        assert_eq!(heapless.as_slice(), fixed.as_slice());
+        assert_eq!(heapless.remaining(), 64 - fixed.len());
+
+        let init = HeaplessPacketBuilder::<16>::new()
+            .init(0x00, "bt_rpc")
+            .unwrap();
+        assert_eq!(
+            init.as_slice(),
+            PacketBuilder::<16>::new()
+                .init(0x00, "bt_rpc")
+                .unwrap()
+                .as_slice()
+        );
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_heapless_builder_cap() {
+        let packet = HeaplessPacketBuilder::<7>::new()
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .unwrap()
+            .cbor_bytes(&[0xAA])
+            .unwrap();
+        assert_eq!(packet.remaining(), 0);
+        assert!(matches!(packet.cbor_null(), Err(CborError::BufferTooSmall)));
+
+        assert!(matches!(
+            HeaplessPacketBuilder::<4>::new().command(0x00, 0x00, 0xFF, 0x00, 0x00),
+            Err(CborError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_cbor_tag_encoding() {
+        // Tag 2 (unsigned bignum) over a 2-byte string: 0xC2 0x42 ...
+        let packet = PacketBuilder::<16>::new()
+            .cbor_tag(2)
+            .unwrap()
+            .cbor_bytes(&[0x01, 0x00])
+            .unwrap();
+        assert_eq!(packet.as_slice(), &[0xC2, 0x42, 0x01, 0x00]);
+
+        // Tags above 23 take a length-prefixed argument
+        let packet = PacketBuilder::<16>::new().cbor_tag(64).unwrap();
+        assert_eq!(packet.as_slice(), &[0xD8, 0x40]);
+
+        let mut buf = [0u8; 16];
+        let packet = PacketWriter::new(&mut buf).cbor_tag(2).unwrap();
+        assert_eq!(packet.as_slice(), &[0xC2]);
+    }
+
+    #[test]
+    fn test_cbor_raw_appends_at_pos() {
+        let packet = PacketBuilder::<16>::new()
+            .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+            .cbor_raw(&[0x18, 0x1C])
+            .unwrap();
+        assert_eq!(packet.len(), HEADER_LEN + 2);
+        assert_eq!(&packet.as_slice()[HEADER_LEN..], &[0x18, 0x1C]);
+
+        let packet = packet.cbor_null().unwrap();
+        assert_eq!(packet.as_slice()[HEADER_LEN + 2], 0xF6);
+
+        assert_eq!(
+            PacketBuilder::<6>::new()
+                .command(0x00, 0x00, 0xFF, 0x00, 0x00)
+                .cbor_raw(&[0x18, 0x1C])
+                .err(),
+            Some(CborError::BufferTooSmall)
+        );
+
+        let mut buf = [0u8; 4];
+        let packet = PacketWriter::new(&mut buf).cbor_raw(&[0x18, 0x1C]).unwrap();
+        assert_eq!(packet.as_slice(), &[0x18, 0x1C]);
+    }
+
+    #[test]
+    fn test_packet_type_predicates() {
+        // byte, response, event, event ack, error report, init, command
+        let table = [
+            (0x00, false, true, false, false, false, false),
+            (0x01, true, false, false, false, false, false),
+            (0x02, false, false, true, false, false, false),
+            (0x03, false, false, false, true, false, false),
+            (0x04, false, false, false, false, true, false),
+            (0x80, false, false, false, false, false, true),
+            // Command from context 1, not mistaken for a response
+            (0x81, false, false, false, false, false, true),
+            (0x05, false, false, false, false, false, false),
+        ];
+        for (byte, response, event, event_ack, error_report, init, command) in table {
+            assert_eq!(PacketType::is_response(byte), response, "{byte:#04x}");
+            assert_eq!(PacketType::is_event(byte), event, "{byte:#04x}");
+            assert_eq!(PacketType::is_event_ack(byte), event_ack, "{byte:#04x}");
+            assert_eq!(
+                PacketType::is_error_report(byte),
+                error_report,
+                "{byte:#04x}"
+            );
+            assert_eq!(PacketType::is_init(byte), init, "{byte:#04x}");
+            assert_eq!(PacketType::is_command(byte), command, "{byte:#04x}");
+        }
+    }
 }
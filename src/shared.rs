@@ -0,0 +1,120 @@
+//! Sharing one `Ble` between tasks
+//!
+//! Every `Ble` method takes `&mut self`, so tasks that want to issue commands
+//! against the same remote need to take turns. `SharedBle` puts the client
+//! behind an `embassy_sync` mutex; a task locks it, runs its commands, and
+//! releases it when the guard is dropped. Only one command is ever in flight,
+//! so responses cannot be handed to the wrong task.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+//! use static_cell::StaticCell;
+//!
+//! static BLE: StaticCell<SharedBle<CriticalSectionRawMutex, Uart>> = StaticCell::new();
+//! let ble = BLE.init(SharedBle::new(Ble::new(uart).await?));
+//!
+//! // In any task holding `ble: &'static SharedBle<..>`
+//! ble.lock().await.bt_le_adv_start_default(&ad, &[]).await?;
+//! ```
+
+use core::ops::{Deref, DerefMut};
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+
+use crate::ble::Ble;
+use crate::transport::AsyncTransport;
+
+/// A `Ble` that several tasks can issue commands against
+///
+/// `M` picks the mutex flavour: `CriticalSectionRawMutex` to share across
+/// interrupt priorities or cores, `NoopRawMutex` for tasks on one executor.
+/// Placing it in a `static` requires `Ble<T, RX>: Send`, see [`Ble`].
+pub struct SharedBle<M: RawMutex, T: AsyncTransport, const RX: usize = 256> {
+    ble: Mutex<M, Ble<T, RX>>,
+}
+
+impl<M: RawMutex, T: AsyncTransport, const RX: usize> SharedBle<M, T, RX> {
+    pub const fn new(ble: Ble<T, RX>) -> Self {
+        Self {
+            ble: Mutex::new(ble),
+        }
+    }
+
+    /// Wait until no other task holds the client, then borrow it
+    ///
+    /// The client is released when the returned guard is dropped. Keep the
+    /// guard only as long as the commands that need it, e.g. not across
+    /// `Ble::next_event` loops, or other tasks stall.
+    pub async fn lock(&self) -> BleGuard<'_, M, T, RX> {
+        BleGuard {
+            guard: self.ble.lock().await,
+        }
+    }
+
+    /// Recover the client once it no longer needs to be shared
+    pub fn into_inner(self) -> Ble<T, RX> {
+        self.ble.into_inner()
+    }
+}
+
+/// Exclusive access to the `Ble` inside a `SharedBle`
+///
+/// Dereferences to `Ble`, so commands are called on the guard directly.
+pub struct BleGuard<'a, M: RawMutex, T: AsyncTransport, const RX: usize = 256> {
+    guard: MutexGuard<'a, M, Ble<T, RX>>,
+}
+
+impl<M: RawMutex, T: AsyncTransport, const RX: usize> Deref for BleGuard<'_, M, T, RX> {
+    type Target = Ble<T, RX>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<M: RawMutex, T: AsyncTransport, const RX: usize> DerefMut for BleGuard<'_, M, T, RX> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockTransport, block_on};
+    use core::pin::pin;
+    use core::task::{Context, Waker};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    #[test]
+    fn test_lock_gives_turns_on_one_client() {
+        let transport = MockTransport::new();
+        let ble: Ble<_> = Ble::from_parts(transport.clone(), 0x00, 0x01);
+        let shared = SharedBle::<NoopRawMutex, _>::new(ble);
+
+        // Each command runs in context 0 once the previous one released it
+        transport.push_response(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+        transport.push_response(&[0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+        block_on(async {
+            assert_eq!(shared.lock().await.bt_enable().await.unwrap(), 0);
+            assert_eq!(shared.lock().await.bt_le_adv_stop().await.unwrap(), 0);
+        });
+
+        let sent = transport.sent_packets();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1][1], 0x05);
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let ble: Ble<_> = Ble::from_parts(MockTransport::new(), 0x00, 0x01);
+        let shared = SharedBle::<NoopRawMutex, _>::new(ble);
+
+        let _guard = block_on(shared.lock());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(pin!(shared.lock()).poll(&mut cx).is_pending());
+    }
+}
@@ -0,0 +1,47 @@
+//! Byte-stream transport shared by the framing and buffering tests
+
+use std::vec::Vec;
+
+use crate::transport::{AsyncTransport, TransportError};
+
+/// Byte stream that hands out at most `chunk` bytes per read
+///
+/// Written bytes are appended to `tx`, and `reads` counts calls to `read`.
+pub(crate) struct StreamTransport {
+    pub(crate) rx: Vec<u8>,
+    pub(crate) tx: Vec<u8>,
+    pub(crate) chunk: usize,
+    pub(crate) reads: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct StreamError;
+
+impl TransportError for StreamError {}
+
+impl AsyncTransport for StreamTransport {
+    type Error = StreamError;
+
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        self.reads += 1;
+        let n = self.rx.len().min(buffer.len()).min(self.chunk);
+        buffer[..n].copy_from_slice(&self.rx[..n]);
+        self.rx.drain(..n);
+        Ok(n)
+    }
+}
+
+/// Stream that will deliver `rx` in reads of at most `chunk` bytes
+pub(crate) fn stream(rx: &[u8], chunk: usize) -> StreamTransport {
+    StreamTransport {
+        rx: rx.to_vec(),
+        tx: Vec::new(),
+        chunk,
+        reads: 0,
+    }
+}
@@ -0,0 +1,234 @@
+//! Helpers for testing code built on this crate
+//!
+//! Requires the `testing` feature, which pulls in `std`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let transport = MockTransport::new();
+//! let mut ble = Ble::new(transport.clone()).await?;
+//! transport.clear_sent();
+//!
+//! transport.push_response(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+//! ble.bt_enable().await?;
+//! assert_eq!(transport.sent_packets()[0][1], 0x00);
+//! ```
+//!
+//! Futures that really wait, e.g. for a response pushed from another thread,
+//! can be driven with [`block_on`] and a transport from
+//! [`MockTransport::waiting`].
+
+use core::future::{Future, pending, poll_fn};
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::Wake;
+use std::vec::Vec;
+
+use crate::packet::PacketType;
+use crate::transport::{AsyncTransport, TransportError};
+
+/// Error type of `MockTransport`, which never fails
+#[derive(Debug)]
+pub struct MockError;
+
+impl TransportError for MockError {}
+
+/// In-memory transport that records written packets and replays canned ones
+///
+/// Clones share the same state, so keep a clone to inspect the traffic after
+/// moving the transport into a client. Every `write` is recorded as one
+/// packet and every `read` returns the next queued response, or 0 bytes once
+/// the queue is empty.
+///
+/// Init requests are answered like the remote would, assigning group ID 0x00
+/// to every group, so `Ble::new` completes without any setup.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    responses: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    /// Task to wake when a response is pushed, set by a waiting `read`
+    waker: Arc<Mutex<Option<Waker>>>,
+    /// Whether `read` waits for a response instead of returning 0 bytes
+    wait_for_data: bool,
+    /// Number of upcoming reads that never complete
+    stalls: Arc<Mutex<usize>>,
+    /// Number of `flush` calls so far
+    flushes: Arc<Mutex<usize>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a transport whose `read` waits until a response is pushed
+    ///
+    /// Drive it with [`block_on`] and push responses from another thread.
+    pub fn waiting() -> Self {
+        Self {
+            wait_for_data: true,
+            ..Self::default()
+        }
+    }
+
+    /// Queue `bytes` to be returned by a later `read`
+    pub fn push_response(&self, bytes: &[u8]) {
+        self.responses.lock().unwrap().push_back(bytes.to_vec());
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+    /// Packets written so far, oldest first
+    pub fn sent_packets(&self) -> Vec<Vec<u8>> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Forget the packets written so far, e.g. the init handshake
+    pub fn clear_sent(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+
+    /// Make the next `count` reads never complete
+    ///
+    /// Lets tests drive `with_timeout` and retry paths; wrap the stalled call
+    /// in `with_timeout` or it never returns.
+    pub fn stall_reads(&self, count: usize) {
+        *self.stalls.lock().unwrap() = count;
+    }
+
+    /// Number of times `flush` was called
+    pub fn flush_count(&self) -> usize {
+        *self.flushes.lock().unwrap()
+    }
+}
+
+impl AsyncTransport for MockTransport {
+    type Error = MockError;
+
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        self.sent.lock().unwrap().push(data.to_vec());
+
+        // Init response: header, max/min version 0, then the group name. Only
+        // init requests, whose destination group is still unknown, are answered
+        if data.len() > 7 && PacketType::is_init(data[0]) && data[4] == 0xFF {
+            let mut response = std::vec![0x04, 0x00, 0xFF, data[3], 0x00, 0x00, 0x00];
+            response.extend_from_slice(&data[7..]);
+            self.responses.lock().unwrap().push_back(response);
+        }
+        Ok(data.len())
+    }
+
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        let stall = {
+            let mut stalls = self.stalls.lock().unwrap();
+            let stall = *stalls > 0;
+            *stalls = stalls.saturating_sub(1);
+            stall
+        };
+        if stall {
+            pending::<()>().await;
+        }
+
+        let response = poll_fn(|cx| {
+            // The queue stays locked while the waker is stored, so a concurrent
+            // `push_response` cannot slip in between and miss it
+            let mut responses = self.responses.lock().unwrap();
+            match responses.pop_front() {
+                Some(response) => Poll::Ready(Some(response)),
+                None if self.wait_for_data => {
+                    *self.waker.lock().unwrap() = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => Poll::Ready(None),
+            }
+        })
+        .await;
+        let Some(response) = response else {
+            return Ok(0);
+        };
+        let len = response.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&response[..len]);
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        *self.flushes.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+/// Run `fut` to completion on the current thread
+///
+/// When the future is pending the thread sleeps until its waker fires, so
+/// futures that wait on another thread (e.g. a `MockTransport::waiting` read)
+/// make progress. Blocks forever if nothing ever wakes the future.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let (tx, rx) = mpsc::channel();
+    let waker = Waker::from(Arc::new(ChannelWaker(tx)));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // The sender lives in `waker`, so this only returns once woken
+        rx.recv().unwrap();
+    }
+}
+
+/// Waker that signals `block_on` through a channel
+struct ChannelWaker(Sender<()>);
+
+impl Wake for ChannelWaker {
+    fn wake(self: Arc<Self>) {
+        let _ = self.0.send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ble::Ble;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_transport_drives_ble() {
+        let transport = MockTransport::new();
+        let mut ble = block_on(Ble::new(transport.clone())).unwrap();
+        assert_eq!(transport.sent_packets().len(), 2);
+        transport.clear_sent();
+
+        transport.push_response(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+        assert_eq!(block_on(ble.bt_enable()).unwrap(), 0);
+        assert_eq!(
+            transport.sent_packets(),
+            [std::vec![
+                0x80, 0x00, 0xFF, 0x00, 0x00, 0x18, 0x1C, 0x18, 0x1C, 0xF6
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_block_on_waits_for_response() {
+        let transport = MockTransport::waiting();
+        let mut ble = block_on(Ble::new(transport.clone())).unwrap();
+
+        // The response arrives only after bt_enable is already waiting
+        let remote = transport.clone();
+        let handle = thread::spawn(move || {
+            while remote.sent_packets().len() < 3 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            remote.push_response(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF6]);
+        });
+
+        assert_eq!(block_on(ble.bt_enable()).unwrap(), 0);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+//! Timeouts for RPC operations
+//!
+//! The crate is `no_std` and does not depend on a particular timer, so
+//! timeouts are expressed by racing an operation against a user-supplied
+//! delay future.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use embassy_time::{Duration, Timer};
+//! use nrf_rpc::with_timeout;
+//!
+//! // Fails with RpcError::Timeout if the remote does not answer within 100ms
+//! let code = with_timeout(Timer::after(Duration::from_millis(100)), ble.bt_enable()).await?;
+//! ```
+
+use core::future::{Future, poll_fn};
+use core::pin::pin;
+use core::task::Poll;
+
+use crate::RpcError;
+
+/// Run `fut` until it completes or `delay` elapses
+///
+/// Returns `RpcError::Timeout` if `delay` completes first; `fut` is dropped at
+/// that point. A command cancelled this way keeps its context ID reserved
+/// until its late response arrives and is dropped, so that response cannot be
+/// mistaken for the reply to a later command.
+pub async fn with_timeout<F, D, T>(delay: D, fut: F) -> Result<T, RpcError>
+where
+    F: Future<Output = Result<T, RpcError>>,
+    D: Future,
+{
+    let mut fut = pin!(fut);
+    let mut delay = pin!(delay);
+
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+            return Poll::Ready(output);
+        }
+        if delay.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Err(RpcError::Timeout));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::{pending, ready};
+    use core::task::{Context, Waker};
+
+    fn poll_once<F: Future>(f: F) -> Poll<F::Output> {
+        let mut cx = Context::from_waker(Waker::noop());
+        pin!(f).poll(&mut cx)
+    }
+
+    #[test]
+    fn test_completes_before_timeout() {
+        let result = poll_once(with_timeout(pending::<()>(), ready(Ok::<_, RpcError>(7))));
+        assert!(matches!(result, Poll::Ready(Ok(7))));
+    }
+
+    #[test]
+    fn test_times_out() {
+        let result = poll_once(with_timeout(ready(()), pending::<Result<i32, RpcError>>()));
+        assert!(matches!(result, Poll::Ready(Err(RpcError::Timeout))));
+    }
+
+    #[test]
+    fn test_pending_while_both_pending() {
+        let result = poll_once(with_timeout(
+            pending::<()>(),
+            pending::<Result<i32, RpcError>>(),
+        ));
+        assert!(result.is_pending());
+    }
+}
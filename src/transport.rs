@@ -9,7 +9,16 @@
 use core::fmt;
 
 /// Error trait for transport implementations
-pub trait TransportError: fmt::Debug {}
+pub trait TransportError: fmt::Debug {
+    /// Buffer size needed for a packet that did not fit, if that is the error
+    ///
+    /// Transports that know the incoming length up front (e.g.
+    /// `FramedTransport`) report it here, and the client surfaces it as
+    /// `RpcError::ResponseTooLarge`. Defaults to `None`.
+    fn needed_len(&self) -> Option<usize> {
+        None
+    }
+}
 
 /// Async transport for sending/receiving raw bytes over UART
 ///
@@ -53,4 +62,29 @@ pub trait AsyncTransport {
     /// Returns the number of bytes read. May return fewer bytes than
     /// the buffer size if data is not immediately available.
     async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Read until `buffer` is full, calling `read` as many times as needed
+    ///
+    /// A zero-length read is treated as end of stream. Returns the number of
+    /// bytes read, which is less than `buffer.len()` only if the stream ended
+    /// first, so callers can tell a clean end from a truncated read.
+    async fn read_exact(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let n = self.read(&mut buffer[filled..]).await?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        Ok(filled)
+    }
+
+    /// Flush any buffered outgoing bytes to the wire
+    ///
+    /// Called after every packet write. The default implementation does nothing,
+    /// which is correct for transports that do not buffer writes.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
@@ -0,0 +1,12 @@
+//! nRF RPC protocol version
+//!
+//! Init packets carry the version the sender speaks, and the remote answers
+//! with the range it supports. The handshake fails with
+//! `RpcError::VersionMismatch` if that range does not overlap
+//! `PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX`.
+
+/// Oldest protocol version this crate can talk to
+pub const PROTOCOL_VERSION_MIN: u8 = 0x00;
+
+/// Newest protocol version this crate speaks, sent in init packets
+pub const PROTOCOL_VERSION_MAX: u8 = 0x00;
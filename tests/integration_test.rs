@@ -5,61 +5,12 @@
 //! nrf/samples/nrf_rpc/protocols_serialization/client/src/bt_test_shell.c
 
 use nrf_rpc::ble::{
-    Ble, BtData, BtLeAdvParam, BT_DATA_FLAGS, BT_DATA_NAME_COMPLETE, BT_LE_AD_GENERAL,
-    BT_LE_AD_NO_BREDR,
+    AdvSetHandle, Ble, BleCommand, BtAddrLe, BtData, BtLeAdvOpt, BtLeAdvParam, BtLeScanParam, CommandResult, ConnHandle, BT_ADDR_LE_PUBLIC, BT_DATA_FLAGS, BT_GAP_LE_PHY_2M, BT_GAP_LE_PHY_CODED, BT_DATA_NAME_COMPLETE,
+    BT_HCI_ERR_REMOTE_USER_TERM_CONN, BT_LE_AD_GENERAL, BT_LE_AD_NO_BREDR,
 };
-use nrf_rpc::{AsyncTransport, TransportError};
-use std::sync::{Arc, Mutex};
-
-/// Mock error type
-#[derive(Debug)]
-struct MockError;
-
-impl core::fmt::Display for MockError {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "Mock transport error")
-    }
-}
-
-impl TransportError for MockError {}
-
-/// Mock UART transport that records all written packets
-#[derive(Clone)]
-struct MockUart {
-    sent_packets: Arc<Mutex<Vec<Vec<u8>>>>,
-}
-
-impl MockUart {
-    fn new() -> Self {
-        Self {
-            sent_packets: Arc::new(Mutex::new(Vec::new())),
-        }
-    }
-
-    fn get_sent_packets(&self) -> Vec<Vec<u8>> {
-        self.sent_packets.lock().unwrap().clone()
-    }
-
-    fn clear_packets(&self) {
-        self.sent_packets.lock().unwrap().clear();
-    }
-}
-
-impl AsyncTransport for MockUart {
-    type Error = MockError;
-
-    async fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
-        // Log the packet being sent
-        println!("MockUart: Sending {} bytes: {:02X?}", data.len(), data);
-        self.sent_packets.lock().unwrap().push(data.to_vec());
-        Ok(())
-    }
-
-    async fn read(&mut self, _buffer: &mut [u8]) -> Result<usize, Self::Error> {
-        // For these tests, we don't simulate responses
-        Ok(0)
-    }
-}
+use nrf_rpc::packet::CborError;
+use nrf_rpc::{AsyncTransport, RpcError};
+use nrf_rpc::test_util::{block_on, MockTransport};
 
 /// Helper to convert hex string to bytes
 fn hex_to_bytes(hex: &str) -> Vec<u8> {
@@ -67,64 +18,28 @@ fn hex_to_bytes(hex: &str) -> Vec<u8> {
         .replace("{", "")
         .replace("}", "")
         .replace("0x", "")
-        .trim()
         .split_whitespace()
         .map(|s| u8::from_str_radix(s, 16).unwrap())
         .collect()
 }
 
-/// Minimal async runtime for tests - just polls futures to completion
-fn block_on<F: core::future::Future>(mut f: F) -> F::Output {
-    use std::pin::Pin;
-    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
-
-    // Create a no-op waker
-    unsafe fn clone(_: *const ()) -> RawWaker {
-        RawWaker::new(std::ptr::null(), &VTABLE)
-    }
-    unsafe fn wake(_: *const ()) {}
-    unsafe fn wake_by_ref(_: *const ()) {}
-    unsafe fn drop(_: *const ()) {}
-
-    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
-
-    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
-    let mut context = Context::from_waker(&waker);
-    
-    // Pin the future
-    let mut pinned = unsafe { Pin::new_unchecked(&mut f) };
-    
-    // Poll until complete
-    loop {
-        match pinned.as_mut().poll(&mut context) {
-            Poll::Ready(output) => return output,
-            Poll::Pending => {
-                // In real async runtime this would yield, but our futures complete immediately
-                panic!("Future didn't complete immediately - tests need a real async runtime");
-            }
-        }
-    }
-}
-
 #[test]
 fn test_bt_enable_generates_correct_packet() {
     block_on(async {
         // From trace: bt_enable() generates this packet
-        // Note: In real usage with responses, group IDs would be 0x00
-        // but our mock doesn't simulate responses, so they stay at 0xFF
-        let expected_packet = hex_to_bytes("80 00 FF FF FF 18 1C 18 1C F6");
+        let expected_packet = hex_to_bytes("80 00 FF 00 00 18 1C 18 1C F6");
 
-        let uart = MockUart::new();
+        let uart = MockTransport::new();
         let uart_clone = uart.clone(); // Keep a reference to check packets
         
         // new() automatically initializes RPC and sends 2 init packets
         let mut ble = Ble::new(uart).await.ok().unwrap();
-        uart_clone.clear_packets();
+        uart_clone.clear_sent();
         
         // Call bt_enable
         ble.bt_enable().await.ok();
         
-        let packets = uart_clone.get_sent_packets();
+        let packets = uart_clone.sent_packets();
         assert_eq!(packets.len(), 1, "Expected 1 packet from bt_enable");
         
         assert_eq!(packets[0], expected_packet,
@@ -133,30 +48,55 @@ fn test_bt_enable_generates_correct_packet() {
     });
 }
 
+#[test]
+fn test_bt_disable_generates_correct_packet() {
+    block_on(async {
+        // bt_disable() takes no arguments, so the payload is just the terminator
+        let expected_packet = hex_to_bytes("80 01 FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        // The mock never answers commands, which is reported as such
+        let result = ble.bt_disable().await;
+        assert!(matches!(result, Err(RpcError::NoData)));
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_disable");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_disable packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
 #[test]
 fn test_bt_le_adv_start_generates_correct_packet() {
     block_on(async {
         // From trace: "bt advertise on" command generates this packet
-        // Note: In real usage with responses, group IDs would be 0x00
-        // but our mock doesn't simulate responses, so they stay at 0xFF
         let expected_packet = hex_to_bytes(
-            "80 04 FF FF FF 18 20 00 00 00 03 18 A0 18 F0 F6 \
+            "80 04 FF 00 00 18 20 00 00 00 03 18 A0 18 F0 F6 \
              01 01 01 41 06 01 09 09 49 4E 6F 72 64 69 63 5F 50 53 F6"
         );
 
-        let uart = MockUart::new();
+        let uart = MockTransport::new();
         let uart_clone = uart.clone(); // Keep a reference to check packets
         
         // new() automatically initializes RPC and sends 2 init packets
         let mut ble = Ble::new(uart).await.ok().unwrap();
-        uart_clone.clear_packets();
+        uart_clone.clear_sent();
         
         // Call bt_le_adv_start with the same parameters as the trace
         let param = BtLeAdvParam {
             id: 0,
             sid: 0,
             secondary_max_skip: 0,
-            options: 0x03,  // BT_LE_ADV_OPT_CONNECTABLE | connectable-something
+            options: BtLeAdvOpt::CONNECTABLE | BtLeAdvOpt::ONE_TIME,
             interval_min: 160,
             interval_max: 240,
             peer: None,
@@ -174,7 +114,7 @@ fn test_bt_le_adv_start_generates_correct_packet() {
         
         ble.bt_le_adv_start(&param, &ad, &sd).await.ok();
         
-        let packets = uart_clone.get_sent_packets();
+        let packets = uart_clone.sent_packets();
         assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_adv_start");
         
         assert_eq!(packets[0], expected_packet,
@@ -182,3 +122,728 @@ fn test_bt_le_adv_start_generates_correct_packet() {
             expected_packet, packets[0]);
     });
 }
+
+#[test]
+fn test_bt_le_adv_stop_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 05 FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_adv_stop().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_adv_stop");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_le_adv_stop packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_set_name_generates_correct_packet() {
+    block_on(async {
+        // "Nordic_PS" is encoded as a 9-byte CBOR text string (0x69)
+        let expected_packet = hex_to_bytes("80 02 FF 00 00 69 4E 6F 72 64 69 63 5F 50 53 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_set_name("Nordic_PS").await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_set_name");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_set_name packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_set_name_rejects_oversized_name() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let name = "N".repeat(128);
+        let result = ble.bt_set_name(&name).await;
+
+        assert!(matches!(
+            result,
+            Err(RpcError::Cbor(CborError::BufferTooSmall))
+        ));
+        assert!(uart_clone.sent_packets().is_empty());
+    });
+}
+
+#[test]
+fn test_bt_get_name_generates_correct_packet() {
+    block_on(async {
+        // The output buffer size is sent so the remote can bound the name
+        let expected_packet = hex_to_bytes("80 03 FF 00 00 18 20 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let mut name = [0u8; 32];
+        ble.bt_get_name(&mut name).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_get_name");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_get_name packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_le_scan_start_generates_correct_packet() {
+    block_on(async {
+        // Active scan: type 1, filter duplicates, interval 0x60, window 0x30
+        let expected_packet = hex_to_bytes("80 06 FF 00 00 01 01 18 60 18 30 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_scan_start(&BtLeScanParam::active()).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_scan_start");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_le_scan_start packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_le_scan_stop_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 07 FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_scan_stop().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_scan_stop");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_le_scan_stop packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_conn_disconnect_generates_correct_packet() {
+    block_on(async {
+        // Connection handle first, then the HCI reason
+        let expected_packet = hex_to_bytes("80 09 FF 00 00 03 13 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_conn_disconnect(ConnHandle(3), BT_HCI_ERR_REMOTE_USER_TERM_CONN)
+            .await
+            .ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_conn_disconnect");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_conn_disconnect packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_small_response_buffer_generates_same_packet() {
+    block_on(async {
+        // The response buffer size doesn't change what goes on the wire
+        let expected_packet = hex_to_bytes("80 01 FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::<_, 32>::with_buffer_size(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_disable().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_disable");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_id_create_without_address_or_irk() {
+    block_on(async {
+        // Both arguments absent are encoded as null, followed by the terminator
+        let expected_packet = hex_to_bytes("80 0A FF 00 00 F6 F6 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_id_create(None, None).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_id_create");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_id_create packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_id_create_with_address_and_irk() {
+    block_on(async {
+        // Address as a 7-byte string (type + address), then the 16-byte IRK
+        let expected_packet = hex_to_bytes(
+            "80 0A FF 00 00 47 01 06 05 04 03 02 C1 \
+             50 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F F6",
+        );
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let addr: BtAddrLe = "C1:02:03:04:05:06 (random)".parse().unwrap();
+        let irk = core::array::from_fn(|i| i as u8);
+        ble.bt_id_create(Some(&addr), Some(&irk)).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_id_create");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_rand_generates_correct_packet() {
+    block_on(async {
+        // The requested length is the only argument
+        let expected_packet = hex_to_bytes("80 0C FF 00 00 10 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let mut nonce = [0u8; 16];
+        ble.bt_rand(&mut nonce).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_rand");
+
+        assert_eq!(
+            packets[0], expected_packet,
+            "bt_rand packet mismatch\nExpected: {:02X?}\nGot:      {:02X?}",
+            expected_packet, packets[0]
+        );
+    });
+}
+
+#[test]
+fn test_bt_is_ready_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 0E FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_is_ready().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_is_ready");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_conn_unref_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 0F FF 00 00 03 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_conn_unref(ConnHandle(3)).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_conn_unref");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_le_oob_get_local_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 16 FF 00 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_oob_get_local(0).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_oob_get_local");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_le_ext_adv_create_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 1E FF 00 00 00 00 00 00 01 18 A0 18 F0 F6 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 01 F6"));
+        let set = ble.bt_le_ext_adv_create(&BtLeAdvParam::connectable()).await.unwrap();
+        assert_eq!(set, AdvSetHandle(1));
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_ext_adv_create");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_le_ext_adv_start_stop_generate_correct_packets() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_ext_adv_start(AdvSetHandle(1)).await.ok();
+        ble.bt_le_ext_adv_stop(AdvSetHandle(1)).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 2, "Expected start and stop packets");
+        assert_eq!(packets[0], hex_to_bytes("80 1F FF 00 00 01 F6"));
+        assert_eq!(packets[1], hex_to_bytes("80 20 FF 00 00 01 F6"));
+    });
+}
+
+#[test]
+fn test_stale_conn_handle_is_rejected() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap().with_conn_validation();
+        uart_clone.clear_sent();
+
+        let result = ble.bt_conn_disconnect(ConnHandle(3), 0x13).await;
+        assert!(matches!(result, Err(RpcError::InvalidHandle)));
+        assert!(uart_clone.sent_packets().is_empty());
+    });
+}
+
+#[test]
+fn test_bt_le_filter_accept_list_add_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 17 FF 00 00 47 00 11 22 33 44 55 66 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let peer = BtAddrLe {
+            addr_type: BT_ADDR_LE_PUBLIC,
+            addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+        };
+        ble.bt_le_filter_accept_list_add(&peer).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_filter_accept_list_add");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_le_filter_accept_list_remove_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 18 FF 00 00 47 00 11 22 33 44 55 66 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        let peer = BtAddrLe {
+            addr_type: BT_ADDR_LE_PUBLIC,
+            addr: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+        };
+        ble.bt_le_filter_accept_list_remove(&peer).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_filter_accept_list_remove");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_le_filter_accept_list_clear_generates_correct_packet() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 19 FF 00 00 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_filter_accept_list_clear().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_filter_accept_list_clear");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_ready_waits_for_ready_event() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        // An unrelated disconnect event arrives first, then bt_ready with err 0
+        uart_clone.push_response(&hex_to_bytes("00 02 FF 00 00 03 13 F6"));
+        uart_clone.push_response(&hex_to_bytes("00 03 FF 00 00 00 F6"));
+        ble.bt_ready().await.unwrap();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 2, "Expected an ack for each event");
+        assert_eq!(packets[1], hex_to_bytes("02 03 FF 00 00"));
+
+        // bt_ready with -EIO
+        uart_clone.push_response(&hex_to_bytes("00 03 FF 00 00 24 F6"));
+        assert!(matches!(ble.bt_ready().await, Err(RpcError::Remote(-5))));
+    });
+}
+
+#[test]
+fn test_bt_le_set_chan_map_generates_correct_packet() {
+    block_on(async {
+        // Channel map is a 5-byte string: 0x45 header
+        let expected_packet = hex_to_bytes("80 1A FF 00 00 45 00 FF FF FF 1F F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_le_set_chan_map(&[0x00, 0xFF, 0xFF, 0xFF, 0x1F]).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_le_set_chan_map");
+        assert_eq!(packets[0], expected_packet);
+
+        // -EINVAL from the stack
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 35 F6"));
+        let result = ble.bt_le_set_chan_map(&[0x00; 5]).await;
+        assert!(matches!(result, Ok(-22)));
+    });
+}
+
+#[test]
+fn test_bt_gatt_read_long_reads_until_short_chunk() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        // MTU 5 allows 4 bytes per read; the 6-byte value takes two reads
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 44 01 02 03 04 F6"));
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 42 05 06 F6"));
+
+        let mut buf = [0u8; 16];
+        let value = ble.bt_gatt_read_long(ConnHandle(3), 0x0012, 5, &mut buf).await.unwrap();
+        assert_eq!(value, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 2);
+        // scratchpad, conn, handle, offset, length
+        assert_eq!(packets[0], hex_to_bytes("80 1B FF 00 00 04 03 12 00 04 F6"));
+        assert_eq!(packets[1], hex_to_bytes("80 1B FF 00 00 04 03 12 04 04 F6"));
+    });
+}
+
+#[test]
+fn test_bt_gatt_read_long_reports_full_buffer() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+
+        // The buffer fills after one read, and the probe finds another byte
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 44 01 02 03 04 F6"));
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 41 05 F6"));
+
+        let mut buf = [0u8; 4];
+        let result = ble.bt_gatt_read_long(ConnHandle(3), 0x0012, 23, &mut buf).await;
+        assert!(matches!(result, Err(RpcError::ResponseTooLarge { needed: 5 })));
+    });
+}
+
+#[test]
+fn test_bt_gatt_read_long_rejects_buffer_past_offset_range() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        // Offsets are 16-bit, so reading past this buffer needs offset 0x10000
+        let mut buf = vec![0u8; usize::from(u16::MAX) + 1];
+        let result = ble.bt_gatt_read_long(ConnHandle(3), 0x0012, 23, &mut buf).await;
+        assert!(matches!(result, Err(RpcError::InvalidArgument)));
+        assert!(uart_clone.sent_packets().is_empty());
+    });
+}
+
+#[test]
+fn test_bt_le_adv_start_default_uses_stored_param() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let param = BtLeAdvParam::builder().interval_min(0x0640).interval_max(0x0780).build();
+        let mut ble = Ble::new(uart).await.ok().unwrap().with_adv_param(param);
+        uart_clone.clear_sent();
+
+        let ad = [BtData::flags(&[BT_LE_AD_GENERAL | BT_LE_AD_NO_BREDR])];
+        ble.bt_le_adv_start_default(&ad, &[]).await.ok();
+        ble.bt_le_adv_start(&param, &ad, &[]).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0], packets[1]);
+    });
+}
+
+#[test]
+fn test_bt_conn_le_phy_update_generates_correct_packet() {
+    block_on(async {
+        // conn, tx PHYs, rx PHYs
+        let expected_packet = hex_to_bytes("80 1C FF 00 00 03 02 06 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_conn_le_phy_update(
+            ConnHandle(3),
+            BT_GAP_LE_PHY_2M,
+            BT_GAP_LE_PHY_2M | BT_GAP_LE_PHY_CODED,
+        )
+        .await
+        .ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_conn_le_phy_update");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_bt_conn_le_data_len_update_generates_correct_packet() {
+    block_on(async {
+        // conn, tx_max_len 251, tx_max_time 2120
+        let expected_packet = hex_to_bytes("80 1D FF 00 00 03 18 FB 19 08 48 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        ble.bt_conn_le_data_len_update(ConnHandle(3), 251, 2120).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from bt_conn_le_data_len_update");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_from_parts_uses_given_group_ids() {
+    block_on(async {
+        // No handshake, and the packet carries the supplied bt_rpc group ID
+        let expected_packet = hex_to_bytes("80 01 FF 02 02 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble: Ble<_> = Ble::from_parts(uart, 0x02, 0x03);
+        let info = ble.init_info();
+        assert_eq!(info.bt_rpc_group_id, 0x02);
+        assert_eq!(info.rpc_utils_group_id, 0x03);
+
+        ble.bt_disable().await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected only the bt_disable packet");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_execute_enable_matches_bt_enable() {
+    block_on(async {
+        let expected_packet = hex_to_bytes("80 00 FF 00 00 18 1C 18 1C F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble = Ble::new(uart).await.ok().unwrap();
+        uart_clone.clear_sent();
+
+        uart_clone.push_response(&hex_to_bytes("01 00 00 00 00 00 F6"));
+        let result = ble.execute(BleCommand::Enable).await.unwrap();
+        assert_eq!(result, CommandResult::Code(0));
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from execute(Enable)");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_new_drained_discards_stale_bytes() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        // Tail of a packet from before the reset, followed by noise
+        uart_clone.push_response(&hex_to_bytes("18 1C F6"));
+        uart_clone.push_response(&hex_to_bytes("00 FF"));
+
+        let mut scratch = [0u8; 16];
+        let ble = Ble::new_drained(uart, &mut scratch).await.unwrap();
+        assert_eq!(ble.init_info().bt_rpc_group_id, 0x00);
+        assert_eq!(uart_clone.sent_packets().len(), 2);
+    });
+}
+
+#[test]
+fn test_command_before_init_fails_early() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble: Ble<_> = Ble::from_parts(uart, 0xFF, 0xFF);
+        let result = ble.bt_enable().await;
+
+        assert!(matches!(result, Err(RpcError::GroupNotRegistered)));
+        assert!(uart_clone.sent_packets().is_empty());
+    });
+}
+
+#[test]
+fn test_rpc_utils_get_version_uses_rpc_utils_group() {
+    block_on(async {
+        // Sent to the rpc_utils group rather than bt_rpc
+        let expected_packet = hex_to_bytes("80 00 FF 03 03 F6");
+
+        let uart = MockTransport::new();
+        let uart_clone = uart.clone();
+
+        let mut ble: Ble<_> = Ble::from_parts(uart, 0x02, 0x03);
+
+        let mut buf = [0u8; 64];
+        ble.rpc_utils_get_version(&mut buf).await.ok();
+
+        let packets = uart_clone.sent_packets();
+        assert_eq!(packets.len(), 1, "Expected 1 packet from rpc_utils_get_version");
+        assert_eq!(packets[0], expected_packet);
+    });
+}
+
+#[test]
+fn test_into_inner_returns_transport() {
+    block_on(async {
+        let uart = MockTransport::new();
+        let ble: Ble<_> = Ble::from_parts(uart.clone(), 0x00, 0x00);
+
+        let mut uart = ble.into_inner();
+        uart.write(&[0x01]).await.ok();
+        assert_eq!(uart.sent_packets(), [vec![0x01]]);
+    });
+}
@@ -0,0 +1,133 @@
+//! Drives the client with nothing but `core`, the way firmware would.
+//!
+//! The transport and executor below avoid `std` entirely (no `Vec`, `Arc` or
+//! threads), so this test breaks if the crate's public path ever starts to
+//! need an allocator or `std` types.
+
+#![no_std]
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use nrf_rpc::ble::Ble;
+use nrf_rpc::{AsyncTransport, TransportError};
+
+const MAX_PACKET: usize = 32;
+const QUEUE_LEN: usize = 4;
+
+#[derive(Debug)]
+struct MockError;
+
+impl TransportError for MockError {}
+
+/// Fixed-capacity packet queue
+struct Queue {
+    packets: [[u8; MAX_PACKET]; QUEUE_LEN],
+    lens: [usize; QUEUE_LEN],
+    head: usize,
+    count: usize,
+}
+
+impl Queue {
+    const fn new() -> Self {
+        Self {
+            packets: [[0u8; MAX_PACKET]; QUEUE_LEN],
+            lens: [0; QUEUE_LEN],
+            head: 0,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, packet: &[u8]) {
+        assert!(self.count < QUEUE_LEN && packet.len() <= MAX_PACKET);
+        let slot = (self.head + self.count) % QUEUE_LEN;
+        self.packets[slot][..packet.len()].copy_from_slice(packet);
+        self.lens[slot] = packet.len();
+        self.count += 1;
+    }
+
+    fn pop(&mut self, buffer: &mut [u8]) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+        let len = self.lens[self.head];
+        buffer[..len].copy_from_slice(&self.packets[self.head][..len]);
+        self.head = (self.head + 1) % QUEUE_LEN;
+        self.count -= 1;
+        len
+    }
+}
+
+/// Transport that answers the remote's side and keeps the last packet written
+///
+/// Init packets get an init response assigning group ID 0x00, and commands
+/// get a response carrying the integer 0.
+struct CoreMock {
+    rx: Queue,
+    last_sent: [u8; MAX_PACKET],
+    last_sent_len: usize,
+    writes: usize,
+}
+
+impl AsyncTransport for CoreMock {
+    type Error = MockError;
+
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        self.last_sent[..data.len()].copy_from_slice(data);
+        self.last_sent_len = data.len();
+        self.writes += 1;
+
+        // Init response: header, max/min version 0, then the group name
+        if data[0] == 0x04 {
+            let mut response = [0u8; MAX_PACKET];
+            response[..7].copy_from_slice(&[0x04, 0x00, 0xFF, data[3], 0x00, 0x00, 0x00]);
+            let name = &data[7..];
+            response[7..7 + name.len()].copy_from_slice(name);
+            self.rx.push(&response[..7 + name.len()]);
+        } else if data[0] & 0x80 != 0 {
+            let ctx_id = data[0] & 0x7F;
+            self.rx
+                .push(&[0x01, data[1], ctx_id, data[4], data[3], 0x00, 0xF6]);
+        }
+        Ok(data.len())
+    }
+
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.rx.pop(buffer))
+    }
+}
+
+/// Poll a future that is expected to complete without yielding
+///
+/// `test_util::block_on` is not used because it parks the thread on a `std`
+/// channel, and this test must build without `std`. Every read here is
+/// answered immediately, so a single poll is enough.
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut cx = Context::from_waker(Waker::noop());
+    match pin!(f).poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future did not complete immediately"),
+    }
+}
+
+#[test]
+fn test_bt_enable_without_std() {
+    let transport = CoreMock {
+        rx: Queue::new(),
+        last_sent: [0u8; MAX_PACKET],
+        last_sent_len: 0,
+        writes: 0,
+    };
+    let mut ble: Ble<_> = block_on(Ble::new(transport)).unwrap();
+    assert_eq!(ble.init_info().bt_rpc_group_id, 0x00);
+
+    assert_eq!(block_on(ble.bt_enable()).unwrap(), 0);
+
+    let transport = ble.into_inner();
+    assert_eq!(transport.writes, 3);
+    assert_eq!(
+        &transport.last_sent[..transport.last_sent_len],
+        &[0x80, 0x00, 0xFF, 0x00, 0x00, 0x18, 0x1C, 0x18, 0x1C, 0xF6]
+    );
+}